@@ -0,0 +1,221 @@
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+static AIRPORTS: &str = include_str!("../resource/airports.csv");
+
+/// Mean radius of the earth in nautical miles.
+const EARTH_RADIUS_NM: f64 = 3440.065;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceUnit {
+    /// nautical miles
+    #[default]
+    Nm,
+    /// kilometers
+    Km,
+    /// statute miles
+    Sm,
+}
+
+impl fmt::Display for DistanceUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DistanceUnit::Nm => "nm",
+            DistanceUnit::Km => "km",
+            DistanceUnit::Sm => "sm",
+        })
+    }
+}
+
+impl DistanceUnit {
+    /// The matching unit of speed, e.g. knots for nautical miles.
+    pub fn per_hour(self) -> &'static str {
+        match self {
+            DistanceUnit::Nm => "kt",
+            DistanceUnit::Km => "km/h",
+            DistanceUnit::Sm => "mph",
+        }
+    }
+}
+
+/// Converts a distance in nautical miles, which is what everything here is computed in.
+pub fn convert_nm(value: f64, unit: DistanceUnit) -> f64 {
+    match unit {
+        DistanceUnit::Nm => value,
+        DistanceUnit::Km => value * 1.852,
+        DistanceUnit::Sm => value * 1.150_779,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Coordinates {
+    /// Reads coordinates written as `LAT,LON` in decimal degrees, e.g. `47.45,-122.31`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (lat, lon) = s.split_once(',')?;
+        let lat: f64 = lat.trim().parse().ok()?;
+        let lon: f64 = lon.trim().parse().ok()?;
+        ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon))
+            .then_some(Coordinates { lat, lon })
+    }
+
+    /// Great-circle distance in nautical miles, by way of the haversine formula.
+    pub fn distance_to(self, other: Coordinates) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_NM * a.sqrt().asin()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Airport {
+    coordinates: Coordinates,
+    /// 0 for large airports, 1 for medium, 2 for everything else.
+    rank: u8,
+}
+
+/// Finds a waypoint's coordinates by ICAO or IATA identifier, or from a `LAT,LON` written out
+/// in place of one for fields no table will ever have.
+pub fn lookup(ident: &str) -> Option<Coordinates> {
+    if let Some(coordinates) = Coordinates::parse(ident) {
+        return Some(coordinates);
+    }
+
+    table()
+        .get(ident.to_ascii_uppercase().as_str())
+        .map(|airport| airport.coordinates)
+}
+
+/// Finds known identifiers resembling one we don't know, best guesses first.
+///
+/// Candidates must be within two edits (counting a swapped pair of characters as one). Among
+/// equally close candidates, those using the same letters win -- fingers swap keys far more
+/// often than they invent new ones -- and then bigger airports, since that's where people fly.
+pub fn suggest(ident: &str, limit: usize) -> Vec<&'static str> {
+    const MAX_DISTANCE: usize = 2;
+
+    let ident = ident.to_ascii_uppercase();
+    let letters = sorted_bytes(&ident);
+    let mut candidates: Vec<_> = table()
+        .iter()
+        .filter_map(|(&candidate, airport)| {
+            let distance = edit_distance(&ident, candidate);
+            let rearranged = sorted_bytes(candidate) == letters;
+            (distance <= MAX_DISTANCE).then_some((distance, !rearranged, airport.rank, candidate))
+        })
+        .collect();
+
+    candidates.sort_unstable();
+    candidates
+        .into_iter()
+        .take(limit)
+        .map(|(.., candidate)| candidate)
+        .collect()
+}
+
+fn sorted_bytes(s: &str) -> Vec<u8> {
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.sort_unstable();
+    bytes
+}
+
+/// Optimal string alignment distance: Levenshtein, plus transpositions of adjacent characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+
+    // d[i][j] holds the distance between the first i bytes of a and the first j bytes of b.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+/// Sums the great-circle distance between consecutive waypoints, in nautical miles.
+///
+/// Returns `None` if any waypoint can't be found; see `unknown_waypoints` to find out which.
+pub fn route_distance(waypoints: &[String]) -> Option<f64> {
+    let coordinates = waypoints
+        .iter()
+        .map(|waypoint| lookup(waypoint))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(
+        coordinates
+            .windows(2)
+            .map(|leg| leg[0].distance_to(leg[1]))
+            .sum(),
+    )
+}
+
+pub fn unknown_waypoints(waypoints: &[String]) -> Vec<&str> {
+    waypoints
+        .iter()
+        .filter(|waypoint| lookup(waypoint).is_none())
+        .map(String::as_str)
+        .collect()
+}
+
+fn table() -> &'static HashMap<&'static str, Airport> {
+    static TABLE: OnceLock<HashMap<&str, Airport>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = HashMap::new();
+        for line in AIRPORTS.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split(',');
+            let (Some(icao), Some(iata), Some(lat), Some(lon), Some(size)) = (
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+            ) else {
+                continue;
+            };
+            let (Ok(lat), Ok(lon)) = (lat.parse(), lon.parse()) else {
+                continue;
+            };
+
+            let airport = Airport {
+                coordinates: Coordinates { lat, lon },
+                rank: match size {
+                    "L" => 0,
+                    "M" => 1,
+                    _ => 2,
+                },
+            };
+            for ident in [icao, iata] {
+                if !ident.is_empty() {
+                    table.entry(ident).or_insert(airport);
+                }
+            }
+        }
+        table
+    })
+}
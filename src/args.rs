@@ -0,0 +1,660 @@
+use std::path::PathBuf;
+
+use chrono::NaiveTime;
+use clap::{builder::NonEmptyStringValueParser, ArgAction, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+
+use route::{
+    airport::DistanceUnit,
+    date::{DateArg, Window},
+    elapsed::ElapsedTime,
+    stats::GroupBy,
+};
+
+/// a simple flight log
+///
+/// Flights may be added either with the `add` subcommand or by passing the flight's arguments
+/// directly, e.g. `route KSEA KPDX 123`.
+///
+/// Defaults for the editor, distance units, data directory, and aircraft may be set in a TOML
+/// config file (see `route config path`) using the keys `editor`, `units`, `data_dir`,
+/// `default_aircraft`, `fuel_units`, and `note_template`. A named logbook may have its own
+/// `default_aircraft` under `[logbooks.NAME]`. Command line flags override the config file.
+#[derive(Clone, Debug, Parser)]
+#[command(
+    subcommand_negates_reqs = true,
+    override_usage = "route [OPTIONS] <ORIGIN> <WAYPOINTS>... <ELAPSED>\n       route <COMMAND>"
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub add: Option<AddArgs>,
+
+    /// directory holding the logbook
+    ///
+    /// Overrides the default platform data directory. The directory is created if it doesn't
+    /// already exist.
+    ///
+    /// The logbook is found by the first of: this option; ROUTE_DB, which names the database file
+    /// itself; `data_dir` in the config file; the platform data directory.
+    #[arg(long, global = true)]
+    pub data_dir: Option<PathBuf>,
+
+    /// check everything, but don't write to the logbook
+    ///
+    /// When adding a flight, the record that would have been written is printed instead. The
+    /// editor is not opened unless notes were given on the command line.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// print only what was asked for
+    ///
+    /// Confirmations, counts, and warnings are left out; errors still go to stderr. Give it twice
+    /// to also hide the warning about unreadable lines in the logbook.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// show times to the second, as hours+minutes+seconds
+    #[arg(long, global = true)]
+    pub precise: bool,
+
+    /// don't color output, even at a terminal
+    ///
+    /// Setting NO_COLOR does the same.
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    /// use a separate, named logbook
+    ///
+    /// The logbook is kept as `db-NAME.json` in the data directory; without this option, flights
+    /// go to `db.json`. Names may contain letters, digits, `-`, and `_`.
+    #[arg(long, global = true, value_name = "NAME", value_parser = logbook_name)]
+    pub logbook: Option<String>,
+
+    /// units for distances [default: nm]
+    #[arg(long, global = true, value_enum)]
+    pub units: Option<DistanceUnit>,
+
+    /// show times in UTC rather than local time
+    #[arg(long, global = true)]
+    pub utc: bool,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum Command {
+    /// add a flight (the default)
+    #[command(override_usage = "route add [OPTIONS] <ORIGIN> <WAYPOINTS>... <ELAPSED>")]
+    Add(AddArgs),
+
+    /// list stored flights
+    List(ListArgs),
+
+    /// show total logged time and flight count
+    #[command(visible_alias = "total")]
+    Stats(StatsArgs),
+
+    /// check 90-day takeoff and landing currency
+    Currency,
+
+    /// summarize a year of flying
+    ///
+    /// Totals, time by aircraft, the most visited airports, and the longest flight. Years follow
+    /// local time unless --utc is given.
+    Report(ReportArgs),
+
+    /// remove the most recent entry, or the one given by --id
+    #[command(visible_alias = "undo")]
+    Delete(DeleteArgs),
+
+    /// show the most recent flight
+    Last,
+
+    /// edit the notes on a logged flight
+    ///
+    /// Flights are numbered from 1, starting with the oldest entry in the log, so a flight's
+    /// index doesn't change as new flights are added. An id never changes at all.
+    Edit(EditArgs),
+
+    /// add to the notes on the most recent flight
+    ///
+    /// The editor opens with the existing notes shown for reference; whatever you write is added
+    /// to them as a new paragraph.
+    Note,
+
+    /// export flights for use in other software
+    Export(ExportArgs),
+
+    /// add flights from a file
+    ///
+    /// CSV files must have the same columns as `export --format csv` writes. Each row is checked
+    /// as `add` would check it, and flights already in the logbook are skipped, so importing an
+    /// export back into the logbook it came from changes nothing.
+    Import(ImportArgs),
+
+    /// log flights from a file of `add` arguments, one flight per line
+    ///
+    /// Each line holds what would follow `route add`, e.g. `KSEA KPDX 1+10 -a N12345 --notes
+    /// "first solo"`, quoted as a shell would. Blank lines and lines starting with # are skipped.
+    /// No editor is opened, so a flight without --notes or --notes-file is logged without notes.
+    ///
+    /// Lines that fail are reported and left out; the rest are logged together in one write, and
+    /// the command exits with an error if any line failed.
+    Batch(BatchArgs),
+
+    /// add the flights from another logbook file
+    ///
+    /// Flights already in this logbook (same time, route, and elapsed time) are skipped. The
+    /// merged logbook is sorted by date.
+    Merge(MergeArgs),
+
+    /// put the logbook in date order
+    ///
+    /// Backdated and merged flights can land out of order; this rewrites the logbook oldest
+    /// first, which also renumbers flights for edit and export --index.
+    Sort(SortArgs),
+
+    /// copy the logbook to a timestamped file
+    ///
+    /// Unless told otherwise, the backup is written next to the logbook as
+    /// `db-YYYYMMDDHHMMSS.json` (or `.sqlite`, for a SQLite logbook).
+    Backup(BackupArgs),
+
+    /// move old flights out of the logbook
+    ///
+    /// Flights are appended to `archive-YYYY.json` in the data directory by the year they were
+    /// flown (`archive-NAME-YYYY.json` for a named logbook), which keeps the logbook itself quick
+    /// to read.
+    Archive(ArchiveArgs),
+
+    /// check that every entry in the logbook is readable and makes sense
+    ///
+    /// Exits with an error if anything is wrong, which makes it a good thing to run before a
+    /// backup or sync.
+    Verify,
+
+    /// copy the logbook into a SQLite database
+    ///
+    /// The database is written next to the logbook as `db.sqlite` (or `db-NAME.sqlite`) and is
+    /// used from then on. The JSON file is left where it is.
+    #[cfg(feature = "sqlite")]
+    MigrateToSqlite,
+
+    /// list the logbooks in the data directory
+    Logbooks,
+
+    /// print where the logbook is stored
+    #[command(visible_alias = "path")]
+    Where,
+
+    /// inspect the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// print a shell completion script
+    ///
+    /// For example: `route completions zsh > _route`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// print where the config file lives
+    Path,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct ListArgs {
+    /// only flights on or after this date
+    ///
+    /// Accepts an RFC 3339 timestamp or a YYYY-MM-DD date, which is taken to mean midnight UTC.
+    #[arg(long)]
+    pub since: Option<DateArg>,
+
+    /// only flights on or before this date
+    ///
+    /// Accepts an RFC 3339 timestamp or a YYYY-MM-DD date, which is taken to mean the end of that
+    /// day (UTC).
+    #[arg(long)]
+    pub until: Option<DateArg>,
+
+    /// only flights passing through this waypoint
+    #[arg(long, value_name = "CODE")]
+    pub via: Option<String>,
+
+    /// only flights departing from this waypoint
+    #[arg(long, value_name = "CODE")]
+    pub from: Option<String>,
+
+    /// only flights ending at this waypoint
+    #[arg(long, value_name = "CODE")]
+    pub to: Option<String>,
+
+    /// only flights with this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// only flights at least this long
+    ///
+    /// Measured along the route, in the units given by --units. Flights with waypoints of unknown
+    /// position are left out whenever a distance filter is used.
+    #[arg(long, value_name = "DISTANCE")]
+    pub min_distance: Option<f64>,
+
+    /// only flights at most this long
+    #[arg(long, value_name = "DISTANCE")]
+    pub max_distance: Option<f64>,
+
+    /// only simulator sessions
+    #[arg(long, conflicts_with = "no_sim")]
+    pub sim_only: bool,
+
+    /// leave out simulator sessions
+    #[arg(long)]
+    pub no_sim: bool,
+
+    /// only cross-country flights
+    ///
+    /// That is, flights ending more than 50 nm (straight line) from where they started. Flights
+    /// with unknown coordinates are left out.
+    #[arg(long)]
+    pub cross_country_only: bool,
+
+    /// show only the last N flights
+    ///
+    /// Applied after the other filters, so `--since 2024-06-01 --limit 5` shows the five most
+    /// recent flights since June 1.
+    #[arg(long, visible_alias = "tail", value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// show one page of flights, counting from 1 at the oldest
+    ///
+    /// Applied last of all, so it pages through whatever the filters and --limit leave.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub page: Option<u32>,
+
+    /// flights per page
+    #[arg(long, value_name = "N", requires = "page", default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    pub page_size: u32,
+
+    /// sketch each route as ASCII art
+    ///
+    /// Routes with waypoints of unknown position are shown as a plain list instead.
+    #[arg(long, conflicts_with = "format")]
+    pub map: bool,
+
+    /// output format
+    #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+    pub format: ListFormat,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ListFormat {
+    /// a table at a terminal, or one readable block per flight when piped
+    Text,
+    /// a single JSON array
+    Json,
+    /// a GitHub-flavored markdown table
+    Markdown,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct StatsArgs {
+    /// break totals down by calendar period, aircraft, or route
+    ///
+    /// Periods follow local time unless --utc is given. Routes are grouped by where they start
+    /// and end.
+    #[arg(long, value_enum)]
+    pub by: Option<GroupBy>,
+
+    /// with --by route, count a route and its reverse together
+    #[arg(long, requires = "by")]
+    pub bidirectional: bool,
+
+    /// rank airports by number of visits
+    #[arg(long, conflicts_with = "by")]
+    pub airports: bool,
+
+    /// chart hours flown per month
+    #[arg(long, conflicts_with_all = ["by", "airports"])]
+    pub sparkline: bool,
+
+    /// with --sparkline, how many months to chart, ending with this one
+    #[arg(long, requires = "sparkline", default_value_t = 12, value_parser = clap::value_parser!(u32).range(1..=120))]
+    pub months: u32,
+
+    /// only count the trailing window, e.g. 90d, 6w, 12mo, or 1y
+    #[arg(long, value_name = "WINDOW")]
+    pub since: Option<Window>,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct ReportArgs {
+    /// year to report on [default: this year]
+    #[arg(long)]
+    pub year: Option<i32>,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct ExportArgs {
+    /// export format
+    #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+    pub format: ExportFormat,
+
+    /// export a single flight
+    ///
+    /// Flights are numbered from 1, starting with the oldest entry in the log. Required for GPX,
+    /// which describes a single route.
+    #[arg(long, required_if_eq("format", "gpx"))]
+    pub index: Option<usize>,
+
+    /// write to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportFormat {
+    /// comma-separated values, one row per flight
+    Csv,
+    /// a GPX route for a single flight
+    Gpx,
+    /// a KML document with every flight, e.g. for Google Earth
+    Kml,
+    /// an iCalendar file with an event for each flight
+    Ics,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct ImportArgs {
+    /// import format
+    #[arg(long, value_enum, default_value_t = ImportFormat::Csv)]
+    pub format: ImportFormat,
+
+    /// file to import
+    pub file: PathBuf,
+
+    /// skip checking waypoints against the airport table, as with add
+    #[arg(long)]
+    pub no_validate: bool,
+
+    /// import rows that look like duplicates or have other problems add would refuse
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ImportFormat {
+    /// comma-separated values, as written by export
+    Csv,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct BatchArgs {
+    /// file of flights to log
+    pub file: PathBuf,
+}
+
+/// One line of a batch file, parsed as `add` would parse it.
+#[derive(Clone, Debug, Parser)]
+#[command(name = "add", no_binary_name = true)]
+pub struct BatchLine {
+    #[command(flatten)]
+    pub add: AddArgs,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct MergeArgs {
+    /// logbook to merge in, e.g. a db.json from another machine
+    pub other: PathBuf,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct SortArgs {
+    /// keep unreadable entries at the end instead of dropping them
+    #[arg(long)]
+    pub keep_unreadable: bool,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct ArchiveArgs {
+    /// archive flights from before this date
+    ///
+    /// Accepts an RFC 3339 timestamp or a YYYY-MM-DD date, which is taken to mean local midnight
+    /// (or midnight UTC with --utc).
+    #[arg(long)]
+    pub before: DateArg,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct BackupArgs {
+    /// write the backup here instead
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct EditArgs {
+    /// which flight to edit, counting from 1 at the oldest
+    #[arg(required_unless_present = "id")]
+    pub index: Option<usize>,
+
+    /// edit the flight with this id instead
+    ///
+    /// Any prefix will do, like a short git hash, as long as only one flight's id starts with it.
+    #[arg(long, conflicts_with = "index", value_parser = NonEmptyStringValueParser::new())]
+    pub id: Option<String>,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct DeleteArgs {
+    /// delete the flight with this id, or a prefix only it has, instead of the latest
+    #[arg(long, value_parser = NonEmptyStringValueParser::new())]
+    pub id: Option<String>,
+
+    /// skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct AddArgs {
+    /// point of origin
+    #[arg(required_unless_present = "from_json")]
+    pub origin: Option<String>,
+
+    /// waypoints, then elapsed time
+    ///
+    /// A collection of waypoints other than your point of origin. These should appear in order
+    /// and the final waypoint should be your destination. A field without an identifier may be
+    /// given as LAT,LON in decimal degrees, e.g. 47.45,-122.31 (after `--` if the latitude is
+    /// negative).
+    ///
+    /// The elapsed time follows the waypoints, expressed in minutes, hours+minutes, hours:minutes,
+    /// or decimal hours ("123", "2+03", "2:03", or "2.05"). For times to the second, add seconds
+    /// ("0+07+42" or "0:07:42") or give seconds alone ("462s"). It may be left off when --depart
+    /// and --arrive are given.
+    #[arg(required_unless_present = "from_json", value_name = "WAYPOINTS")]
+    pub waypoints: Vec<String>,
+
+    /// also log the flight home, taking this long
+    ///
+    /// The return flight follows the same waypoints in reverse, in the same aircraft and with the
+    /// same tags and landing count. Notes are kept with the outbound flight.
+    #[arg(long = "return", value_name = "ELAPSED")]
+    pub return_elapsed: Option<ElapsedTime>,
+
+    /// departure time, e.g. 14:05
+    ///
+    /// Given with --arrive, the elapsed time is worked out from the two, wrapping past midnight
+    /// if need be. Both times are stored with the flight.
+    #[arg(long, value_name = "HH:MM", value_parser = clock_time, requires = "arrive")]
+    pub depart: Option<NaiveTime>,
+
+    /// arrival time, e.g. 16:08
+    #[arg(long, value_name = "HH:MM", value_parser = clock_time, requires = "depart")]
+    pub arrive: Option<NaiveTime>,
+
+    /// night time
+    ///
+    /// The portion of the flight flown at night, in the same format as the elapsed time.
+    #[arg(long)]
+    pub night: Option<ElapsedTime>,
+
+    /// estimate night time from --depart and the route
+    ///
+    /// Night runs from the end of evening civil twilight to the start of morning civil twilight,
+    /// worked out where the aircraft would have been minute by minute. Departure is read as local
+    /// time on the flight's date. The sun's position is only approximate, so the estimate is shown
+    /// for you to accept or refuse before it's logged.
+    #[arg(long, conflicts_with = "night", requires = "depart")]
+    pub infer_night: bool,
+
+    /// instrument time
+    ///
+    /// The portion of the flight flown in actual or simulated instrument conditions, in the same
+    /// format as the elapsed time.
+    #[arg(long)]
+    pub instrument: Option<ElapsedTime>,
+
+    /// aircraft flown
+    ///
+    /// Usually a registration or tail number, e.g. N12345. Defaults to `default_aircraft` from
+    /// the config file, which may be set per logbook under `[logbooks.NAME]`; pass "" to log a
+    /// flight without one.
+    #[arg(short, long)]
+    pub aircraft: Option<String>,
+
+    /// tag the flight, e.g. training or checkride
+    ///
+    /// May be given more than once. Tags are stored in lowercase, and duplicates are dropped.
+    #[arg(short, long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// log time in a simulator or flight training device
+    ///
+    /// Simulator time is totaled separately from time in an aircraft.
+    #[arg(long)]
+    pub sim: bool,
+
+    /// number of landings
+    ///
+    /// Flights logged without a count are assumed to have made one landing.
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(..=200))]
+    pub landings: Option<u32>,
+
+    /// fuel burned
+    ///
+    /// In gallons or liters, whichever `fuel_units` in the config file says; the number is stored
+    /// as given either way.
+    #[arg(long, value_parser = fuel_quantity)]
+    pub fuel: Option<f64>,
+
+    /// date of the flight, if not today
+    ///
+    /// Accepts an RFC 3339 timestamp or a YYYY-MM-DD date, which is taken to mean local midnight.
+    /// Dates in the future are rejected.
+    #[arg(long, visible_alias = "backdate")]
+    pub date: Option<DateArg>,
+
+    /// skip waypoint validation
+    ///
+    /// By default, waypoints must be ICAO or IATA identifiers found in the bundled airport table.
+    /// Use this for fields missing from the table or non-standard points such as lat/long fixes.
+    #[arg(long)]
+    pub no_validate: bool,
+
+    /// a local flight, departing and landing at the same field
+    ///
+    /// Without this, a route must go somewhere other than its origin, e.g. `route KBFI KBFI 1.2
+    /// --local` for an hour of pattern work.
+    #[arg(long)]
+    pub local: bool,
+
+    /// log the flight even if it looks like a mistake
+    ///
+    /// A flight with the same route and elapsed time as one logged within the past hour is
+    /// normally refused as an accidental double entry, as is a route with the same waypoint twice
+    /// in a row or notes over 64 KiB.
+    #[arg(long)]
+    pub force: bool,
+
+    /// notes on the flight
+    ///
+    /// If this field is left empty, an editor window will open and the user may save a note
+    /// there. Give it more than once for more lines, or pass "-" to read the note from stdin
+    /// instead. Either way, comments are stripped just as they are in the editor, so write \# for
+    /// a literal #.
+    #[arg(short, long)]
+    pub notes: Vec<String>,
+
+    /// start the editor from this file instead of the usual help
+    ///
+    /// Handy for prompting yourself for weather, approaches, and the like. Lines starting with #
+    /// are stripped as usual, and saving the template unchanged leaves the flight without notes.
+    /// Overrides `note_template` in the config file.
+    #[arg(long, value_name = "FILE", conflicts_with = "notes")]
+    pub template: Option<PathBuf>,
+
+    /// read the notes from this file
+    ///
+    /// Comments are stripped just as they are in the editor.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["notes", "template"])]
+    pub notes_file: Option<PathBuf>,
+
+    /// log a flight given as a JSON record, or "-" to read one from stdin
+    ///
+    /// The record takes the shape the logbook stores, as `list --format json` shows, with elapsed,
+    /// night, and instrument times in seconds. Only `waypoints` and `elapsed` are required; a
+    /// record without an `id` or `created` gets a new id and the current time. It's checked just
+    /// as a flight given on the command line would be, and no editor is opened.
+    #[arg(
+        long,
+        value_name = "JSON",
+        conflicts_with_all = [
+            "origin", "waypoints", "return_elapsed", "depart", "arrive", "night", "infer_night",
+            "instrument", "aircraft", "tags", "sim", "landings", "fuel", "date", "notes",
+            "template", "notes_file",
+        ]
+    )]
+    pub from_json: Option<String>,
+
+    /// accept a --from-json record with fields the logbook doesn't know, ignoring them
+    //
+    // `requires` alone lets this through alongside a route, since clap won't demand an argument
+    // that conflicts with one already given.
+    #[arg(long, requires = "from_json", conflicts_with = "origin")]
+    pub lenient: bool,
+}
+
+fn clock_time(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| String::from("expected a time like 14:05"))
+}
+
+fn fuel_quantity(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(fuel) if fuel.is_finite() && fuel > 0.0 => Ok(fuel),
+        _ => Err(String::from(
+            "expected a positive amount of fuel, e.g. 12.5",
+        )),
+    }
+}
+
+fn logbook_name(s: &str) -> Result<String, String> {
+    if !s.is_empty()
+        && s.bytes()
+            .all(|u| u.is_ascii_alphanumeric() || u == b'-' || u == b'_')
+    {
+        Ok(s.to_string())
+    } else {
+        Err(String::from(
+            "logbook names may contain only letters, digits, '-', and '_'",
+        ))
+    }
+}
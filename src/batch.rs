@@ -0,0 +1,69 @@
+//! Batch files: one flight per line, written as the arguments to `route add`.
+
+/// Splits a line into words as a shell would: on whitespace, except inside single or double
+/// quotes, with a backslash escaping the next character (other than inside single quotes).
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(u) = chars.next() {
+        match (quote, u) {
+            (Some(q), u) if u == q => quote = None,
+            (Some('\''), u) => word.get_or_insert_default().push(u),
+            (_, '\\') => match chars.next() {
+                Some(u) => word.get_or_insert_default().push(u),
+                None => return Err(String::from("line ends with a backslash")),
+            },
+            (Some(_), u) => word.get_or_insert_default().push(u),
+            (None, '\'' | '"') => {
+                quote = Some(u);
+                word.get_or_insert_default();
+            }
+            (None, u) if u.is_whitespace() => words.extend(word.take()),
+            (None, u) => word.get_or_insert_default().push(u),
+        }
+    }
+
+    if let Some(quote) = quote {
+        return Err(format!("unclosed {quote}"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_split_on_whitespace() {
+        assert_eq!(
+            split_words("  KSEA   KPDX\t1+10 ").unwrap(),
+            ["KSEA", "KPDX", "1+10"]
+        );
+        assert!(split_words("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn quotes_hold_words_together() {
+        assert_eq!(
+            split_words(r#"--notes "first solo" -a 'N1 2'"#).unwrap(),
+            ["--notes", "first solo", "-a", "N1 2"]
+        );
+        assert_eq!(split_words(r#"--notes """#).unwrap(), ["--notes", ""]);
+        assert_eq!(split_words(r#"a"b c"d"#).unwrap(), ["ab cd"]);
+    }
+
+    #[test]
+    fn backslashes_escape_outside_single_quotes() {
+        assert_eq!(split_words(r#"a\ b "c\"d""#).unwrap(), ["a b", r#"c"d"#]);
+        assert_eq!(split_words(r"'a\b'").unwrap(), [r"a\b"]);
+    }
+
+    #[test]
+    fn unfinished_lines_are_refused() {
+        assert!(split_words(r#"--notes "first solo"#).is_err());
+        assert!(split_words(r"KSEA \").is_err());
+    }
+}
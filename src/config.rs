@@ -0,0 +1,85 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{airport::DistanceUnit, db, error::PathContext};
+
+/// User defaults, read from `config.toml` in the platform config directory.
+///
+/// Every setting is optional, and explicit command line flags take precedence.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub editor: Option<String>,
+    pub units: Option<DistanceUnit>,
+    pub data_dir: Option<PathBuf>,
+    pub default_aircraft: Option<String>,
+    pub fuel_units: Option<FuelUnit>,
+    pub note_template: Option<PathBuf>,
+    /// Settings for named logbooks, under `[logbooks.NAME]`.
+    #[serde(default)]
+    pub logbooks: HashMap<String, LogbookConfig>,
+}
+
+/// Overrides for a single named logbook.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogbookConfig {
+    pub default_aircraft: Option<String>,
+}
+
+/// What fuel is measured in. This is only a label: fuel is stored as the number given.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FuelUnit {
+    #[default]
+    #[serde(alias = "gallons")]
+    Gal,
+    #[serde(alias = "l")]
+    Liters,
+}
+
+impl fmt::Display for FuelUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FuelUnit::Gal => "gal",
+            FuelUnit::Liters => "L",
+        })
+    }
+}
+
+impl Config {
+    /// The aircraft to log when none is given, as set for this logbook or else overall. An empty
+    /// value means none, so a logbook can opt out of the overall default.
+    pub fn default_aircraft(&self, logbook: Option<&str>) -> Option<&str> {
+        logbook
+            .and_then(|name| self.logbooks.get(name))
+            .and_then(|config| config.default_aircraft.as_deref())
+            .or(self.default_aircraft.as_deref())
+            .filter(|aircraft| !aircraft.is_empty())
+    }
+
+    /// Loads the config file, or the defaults if there isn't one.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e).with_path("read", path),
+        };
+
+        toml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bad config file {}: {}", path.display(), e.message()),
+            )
+        })
+    }
+}
+
+pub fn config_path() -> io::Result<PathBuf> {
+    Ok(db::project_dirs()?.config_dir().join("config.toml"))
+}
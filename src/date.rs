@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Local, Months, NaiveDate, NaiveTime, TimeZone, Utc};
+
+#[derive(Debug, thiserror::Error)]
+#[error("expected an RFC 3339 timestamp or a YYYY-MM-DD date")]
+pub struct ParseDateError;
+
+/// A date or instant supplied on the command line.
+///
+/// Bare dates cover a whole (UTC) day, so whether we mean the start or the end of that day
+/// depends on which end of a range the date sits at.
+#[derive(Clone, Copy, Debug)]
+pub enum DateArg {
+    Instant(DateTime<Utc>),
+    Day(NaiveDate),
+}
+
+impl DateArg {
+    pub fn start(self) -> DateTime<Utc> {
+        match self {
+            DateArg::Instant(instant) => instant,
+            DateArg::Day(day) => Utc.from_utc_datetime(&day.and_time(NaiveTime::MIN)),
+        }
+    }
+
+    /// Like `start`, but a bare date means midnight in the local timezone.
+    pub fn start_local(self) -> DateTime<Utc> {
+        match self {
+            DateArg::Instant(instant) => instant,
+            DateArg::Day(day) => {
+                let midnight = day.and_time(NaiveTime::MIN);
+
+                // If midnight doesn't exist locally (thanks, DST), fall back to UTC rather than
+                // refuse the date outright.
+                Local
+                    .from_local_datetime(&midnight)
+                    .earliest()
+                    .map(|local| local.with_timezone(&Utc))
+                    .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+            }
+        }
+    }
+
+    pub fn end(self) -> DateTime<Utc> {
+        match self {
+            DateArg::Instant(instant) => instant,
+            DateArg::Day(day) => {
+                let last = NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999).unwrap();
+                Utc.from_utc_datetime(&day.and_time(last))
+            }
+        }
+    }
+}
+
+impl FromStr for DateArg {
+    type Err = ParseDateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(instant) = DateTime::parse_from_rfc3339(s) {
+            return Ok(DateArg::Instant(instant.with_timezone(&Utc)));
+        }
+
+        NaiveDate::parse_from_str(s, "%F")
+            .map(DateArg::Day)
+            .map_err(|_| ParseDateError)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("expected a count and a unit, e.g. 90d, 6w, 12mo, or 1y")]
+pub struct ParseWindowError;
+
+/// A trailing window of time, e.g. `90d` for the last 90 days.
+///
+/// Days and weeks are fixed lengths; months and years are calendar months, so `1y` from March 1
+/// reaches back to March 1 of the year before.
+#[derive(Clone, Copy, Debug)]
+pub enum Window {
+    Days(u32),
+    Months(u32),
+}
+
+impl Window {
+    /// Where the window opens, counting back from `now`.
+    pub fn start(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Window::Days(days) => chrono::Duration::try_days(days.into())
+                .and_then(|days| now.checked_sub_signed(days))
+                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+            Window::Months(months) => now
+                .checked_sub_months(Months::new(months))
+                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = ParseWindowError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.find(|u: char| !u.is_ascii_digit()).unwrap_or(s.len());
+        let (count, unit) = s.split_at(split);
+        let count: u32 = count.parse().map_err(|_| ParseWindowError)?;
+
+        let window = match unit {
+            "d" => Window::Days(count),
+            "w" => Window::Days(count.checked_mul(7).ok_or(ParseWindowError)?),
+            "mo" => Window::Months(count),
+            "y" => Window::Months(count.checked_mul(12).ok_or(ParseWindowError)?),
+            _ => return Err(ParseWindowError),
+        };
+        Ok(window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absurd_windows_reach_back_to_the_beginning() {
+        let now = Utc::now();
+        for window in ["100000000d", "4294967295d", "300000000y"] {
+            let window: Window = window.parse().unwrap();
+            assert_eq!(window.start(now), DateTime::<Utc>::MIN_UTC);
+        }
+    }
+
+    #[test]
+    fn weeks_are_seven_days() {
+        let now = Utc::now();
+        let window: Window = "2w".parse().unwrap();
+        assert_eq!(now - window.start(now), chrono::Duration::days(14));
+    }
+}
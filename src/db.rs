@@ -0,0 +1,539 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use directories::ProjectDirs;
+
+use crate::{
+    error::PathContext,
+    flight::{self, Flight},
+};
+
+#[cfg(feature = "sqlite")]
+use crate::sqlite;
+
+pub fn project_dirs() -> io::Result<ProjectDirs> {
+    ProjectDirs::from("", "Hack Commons", "route").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not determine data directory",
+        )
+    })
+}
+
+/// Names the database file outright, for tests and one-off logbooks.
+const DB_VAR: &str = "ROUTE_DB";
+
+/// Finds the database file.
+///
+/// In order of precedence: `--data-dir`, then `ROUTE_DB`, then the config file's `data_dir`, then
+/// the platform data directory. `ROUTE_DB` is a file rather than a directory, so it doesn't mix
+/// with named logbooks.
+pub fn get_file_path(
+    data_dir: Option<&Path>,
+    configured_dir: Option<&Path>,
+    logbook: Option<&str>,
+) -> io::Result<PathBuf> {
+    if let Some(path) = env::var_os(DB_VAR).filter(|path| !path.is_empty()) {
+        if data_dir.is_none() {
+            if logbook.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{DB_VAR} names a single logbook; unset it to use --logbook"),
+                ));
+            }
+
+            let path = PathBuf::from(path);
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir).with_path("create", dir)?;
+            }
+            return Ok(path);
+        }
+    }
+
+    let dirs;
+    let dir = match data_dir.or(configured_dir) {
+        Some(dir) => dir,
+        None => {
+            dirs = project_dirs()?;
+            dirs.data_dir()
+        }
+    };
+
+    if !dir.exists() {
+        fs::create_dir_all(dir).with_path("create", dir)?;
+    }
+
+    let path = match logbook {
+        Some(name) => dir.join(format!("db-{name}.json")),
+        None => dir.join("db.json"),
+    };
+
+    // Once a logbook has been migrated, its JSON file is left behind only as a fallback.
+    #[cfg(feature = "sqlite")]
+    {
+        let sqlite = path.with_extension("sqlite");
+        if sqlite.exists() {
+            return Ok(sqlite);
+        }
+    }
+
+    Ok(path)
+}
+
+/// Whether the logbook at `path` is a SQLite database rather than JSON lines.
+pub fn is_sqlite(path: &Path) -> bool {
+    #[cfg(feature = "sqlite")]
+    return sqlite::is_sqlite(path);
+
+    #[cfg(not(feature = "sqlite"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Lists the logbooks in `dir` by name, with `None` standing in for the default logbook.
+///
+/// Backups share the `db-*.json` (or `db-*.sqlite`) pattern, so anything ending in a backup
+/// timestamp is left out.
+pub fn logbooks(dir: &Path) -> io::Result<Vec<Option<String>>> {
+    let mut logbooks = Vec::new();
+    for entry in fs::read_dir(dir).with_path("read", dir)? {
+        let path = entry.with_path("read", dir)?.path();
+        let is_logbook = path.extension().is_some_and(|ext| ext == "json") || is_sqlite(&path);
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        if !is_logbook {
+            continue;
+        }
+
+        if stem == "db" {
+            logbooks.push(None);
+        } else if let Some(name) = stem.strip_prefix("db-") {
+            if !is_backup_timestamp(name.rsplit('-').next().unwrap_or(name)) {
+                logbooks.push(Some(name.to_string()));
+            }
+        }
+    }
+
+    // A migrated logbook has both its files, but it's still the one logbook.
+    logbooks.sort();
+    logbooks.dedup();
+    Ok(logbooks)
+}
+
+fn is_backup_timestamp(s: &str) -> bool {
+    s.len() == 14 && s.bytes().all(|u| u.is_ascii_digit())
+}
+
+/// How long to wait on another `route` process before giving up.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An advisory lock on the database, released when dropped.
+///
+/// The lock is taken on a `.lock` file beside the database rather than the database itself,
+/// because `write_flights` replaces the database file wholesale. Anything that writes takes an
+/// exclusive lock; `list`, `stats`, and the other readers take a shared lock, so they can run
+/// alongside one another but never see a write half-finished.
+pub struct Lock {
+    _file: File,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+pub fn lock(path: &Path, mode: LockMode) -> io::Result<Lock> {
+    let lock_path = path.with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_path("open", &lock_path)?;
+
+    let started = Instant::now();
+    loop {
+        let result = match mode {
+            LockMode::Shared => file.try_lock_shared(),
+            LockMode::Exclusive => file.try_lock(),
+        };
+
+        match result {
+            Ok(()) => return Ok(Lock { _file: file }),
+            Err(TryLockError::Error(e)) => return Err(e),
+            Err(TryLockError::WouldBlock) if started.elapsed() >= LOCK_TIMEOUT => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!(
+                        "timed out waiting for another route process (lock held on {})",
+                        lock_path.display()
+                    ),
+                ));
+            }
+            Err(TryLockError::WouldBlock) => thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+/// Reads the database, skipping unreadable lines and, if `warn` is set, saying which.
+pub fn read_flights(path: &Path, warn: bool) -> io::Result<Vec<Flight>> {
+    let mut flights = Vec::new();
+    for_each_flight(path, warn, |flight| flights.push(flight))?;
+    Ok(flights)
+}
+
+/// Reads the database a flight at a time, handing each to `f` as soon as it's parsed, so memory
+/// stays flat however long the logbook gets. Unreadable lines are skipped as in `read_flights`.
+pub fn for_each_flight(path: &Path, warn: bool, f: impl FnMut(Flight)) -> io::Result<()> {
+    let mut skipped = Vec::new();
+    scan(path, f, |entry| skipped.push(entry.line))?;
+
+    if warn && !skipped.is_empty() {
+        warn_skipped(&skipped);
+    }
+
+    Ok(())
+}
+
+/// A line of the database we couldn't make sense of.
+pub struct Unreadable {
+    pub line: usize,
+    pub raw: Vec<u8>,
+}
+
+/// Reads the most recent flight, which is the last in the file as long as it's kept in order.
+///
+/// Only the end of the file is read, unless the last line can't be parsed; then the whole file is
+/// read after all, to find the flight before it and say which lines were passed over.
+pub fn read_last(path: &Path, warn: bool) -> io::Result<Option<Flight>> {
+    #[cfg(feature = "sqlite")]
+    let json = !sqlite::is_sqlite(path);
+    #[cfg(not(feature = "sqlite"))]
+    let json = true;
+
+    if json {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_path("open", path),
+        };
+        let line = last_line(&mut file).with_path("read", path)?;
+        if line.is_empty() {
+            return Ok(None);
+        }
+        if let Ok(flight) = serde_json::from_slice(&line).and_then(flight::migrate) {
+            return Ok(Some(flight));
+        }
+    }
+
+    let mut last = None;
+    for_each_flight(path, warn, |flight| last = Some(flight))?;
+    Ok(last)
+}
+
+/// How much of the file `last_line` reads at a time, working back from the end.
+const TAIL_CHUNK: u64 = 8 * 1024;
+
+/// Finds the last line of the file that isn't blank, or nothing if every line is.
+fn last_line(file: &mut File) -> io::Result<Vec<u8>> {
+    let mut end = file.metadata()?.len();
+    let mut tail = Vec::new();
+    loop {
+        let start = end.saturating_sub(TAIL_CHUNK);
+        let mut chunk = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.append(&mut tail);
+        tail = chunk;
+
+        let content = tail
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(0, |idx| idx + 1);
+        if let Some(newline) = tail[..content].iter().rposition(|&byte| byte == b'\n') {
+            return Ok(tail[newline + 1..content].to_vec());
+        }
+        if start == 0 {
+            tail.truncate(content);
+            return Ok(tail);
+        }
+        end = start;
+    }
+}
+
+/// Reads the database, handing back any unreadable lines rather than warning about them.
+pub fn read_entries(path: &Path) -> io::Result<(Vec<Flight>, Vec<Unreadable>)> {
+    let mut flights = Vec::new();
+    let mut unreadable = Vec::new();
+    scan(
+        path,
+        |flight| flights.push(flight),
+        |entry| unreadable.push(entry),
+    )?;
+    Ok((flights, unreadable))
+}
+
+/// Walks the database line by line, sorting each into a flight or an unreadable entry.
+fn scan(
+    path: &Path,
+    mut on_flight: impl FnMut(Flight),
+    mut on_unreadable: impl FnMut(Unreadable),
+) -> io::Result<()> {
+    #[cfg(feature = "sqlite")]
+    if sqlite::is_sqlite(path) {
+        return sqlite::for_each_flight(path, on_flight);
+    }
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_path("open", path),
+    };
+
+    // A half-written line shouldn't cost the user their whole logbook, so anything we can't
+    // parse is set aside rather than treated as fatal. We split on raw bytes so that garbage
+    // which isn't even valid UTF-8 lands in the same bucket.
+
+    for (idx, line) in BufReader::new(file).split(b'\n').enumerate() {
+        let line = line.with_path("read", path)?;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        match serde_json::from_slice(&line).and_then(flight::migrate) {
+            Ok(flight) => on_flight(flight),
+            Err(_) => on_unreadable(Unreadable {
+                line: idx + 1,
+                raw: line,
+            }),
+        }
+    }
+
+    Ok(())
+}
+
+fn warn_skipped(lines: &[usize]) {
+    let count = lines.len();
+    let entries = if count == 1 { "entry" } else { "entries" };
+    let lines = lines
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let label = if count == 1 { "line" } else { "lines" };
+    eprintln!("skipped {count} unreadable {entries} ({label} {lines})");
+}
+
+/// A line `verify` objected to, and why.
+pub struct Corrupt {
+    /// The line, or for a SQLite logbook the row counting from the oldest flight.
+    pub line: usize,
+    pub reason: String,
+}
+
+/// Checks every line of the database, returning the number that passed along with the ones
+/// that didn't.
+///
+/// This is stricter than reading: a line that parses still fails if it describes a flight that
+/// couldn't have happened.
+pub fn verify(path: &Path) -> io::Result<(usize, Vec<Corrupt>)> {
+    #[cfg(feature = "sqlite")]
+    if sqlite::is_sqlite(path) {
+        return sqlite::verify(path);
+    }
+
+    let reader = BufReader::new(File::open(path).with_path("open", path)?);
+    let mut valid = 0;
+    let mut corrupt = Vec::new();
+    for (idx, line) in reader.split(b'\n').enumerate() {
+        let line = line.with_path("read", path)?;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        let reason = match serde_json::from_slice(&line).and_then(flight::migrate) {
+            Err(e) => Some(format!("unreadable ({e})")),
+            Ok(flight) => implausible(&flight),
+        };
+
+        match reason {
+            Some(reason) => corrupt.push(Corrupt {
+                line: idx + 1,
+                reason,
+            }),
+            None => valid += 1,
+        }
+    }
+
+    Ok((valid, corrupt))
+}
+
+/// What's wrong with a flight that reads fine but couldn't have happened, if anything.
+pub(crate) fn implausible(flight: &Flight) -> Option<String> {
+    if flight.elapsed <= chrono::Duration::zero() {
+        Some(String::from("elapsed time isn't positive"))
+    } else if flight.waypoints.is_empty() {
+        Some(String::from("no waypoints"))
+    } else if flight.waypoints.iter().any(|waypoint| waypoint.is_empty()) {
+        Some(String::from("empty waypoint"))
+    } else {
+        None
+    }
+}
+
+/// Appends flights to the database.
+///
+/// The records are written with a single call and synced before we return, which keeps the
+/// window for leaving a half-written line behind as small as we can make it without rewriting the
+/// file.
+pub fn append_flights<'a>(
+    path: &Path,
+    flights: impl IntoIterator<Item = &'a Flight>,
+) -> io::Result<()> {
+    #[cfg(feature = "sqlite")]
+    if sqlite::is_sqlite(path) {
+        return sqlite::append_flights(path, flights);
+    }
+
+    let mut lines = String::new();
+    for flight in flights {
+        lines.push_str(&serde_json::to_string(flight)?);
+        lines.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_path("open", path)?;
+    file.write_all(lines.as_bytes())
+        .and_then(|_| file.flush())
+        .and_then(|_| file.sync_all())
+        .with_path("write", path)
+}
+
+/// Replaces the database with the given flights.
+///
+/// The new contents are written to `db.json.tmp` and renamed into place, so a crash leaves either
+/// the old file or the new one -- never half of each.
+pub fn write_flights<'a>(
+    path: &Path,
+    flights: impl IntoIterator<Item = &'a Flight>,
+) -> io::Result<()> {
+    write_entries(path, flights, &[])
+}
+
+/// Replaces the database with the given flights, followed by unreadable lines kept as they were.
+pub fn write_entries<'a>(
+    path: &Path,
+    flights: impl IntoIterator<Item = &'a Flight>,
+    unreadable: &[Unreadable],
+) -> io::Result<()> {
+    #[cfg(feature = "sqlite")]
+    if sqlite::is_sqlite(path) {
+        return sqlite::write_flights(path, flights);
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut writer = BufWriter::new(File::create(&tmp).with_path("create", &tmp)?);
+    for flight in flights {
+        serde_json::to_writer(&mut writer, flight)?;
+        writer.write_all(b"\n").with_path("write", &tmp)?;
+    }
+    for entry in unreadable {
+        writer.write_all(&entry.raw).with_path("write", &tmp)?;
+        writer.write_all(b"\n").with_path("write", &tmp)?;
+    }
+    writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)
+        .and_then(|file| file.sync_all())
+        .with_path("write", &tmp)?;
+
+    fs::rename(&tmp, path).with_path("replace", path)
+}
+
+/// Copies the database to `destination`, returning the number of flights in the copy.
+///
+/// A backup we can't trust is worse than none, so the copy is checked against the original --
+/// the same size, with the same number of readable flights -- and deleted if it doesn't match.
+/// Lines the original can't read are copied as they are, so a logbook with a half-written line
+/// can still be backed up. A backup never replaces an existing file, least of all the logbook.
+pub fn backup(path: &Path, destination: &Path) -> io::Result<usize> {
+    if destination.canonicalize().ok() == Some(path.canonicalize().with_path("open", path)?) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "can't back up a logbook onto itself",
+        ));
+    }
+    if destination.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", destination.display()),
+        ));
+    }
+
+    #[cfg(feature = "sqlite")]
+    if sqlite::is_sqlite(path) {
+        return sqlite::backup(path, destination);
+    }
+
+    // Opened with create_new, so a file that turned up since the check above is still left alone.
+    let mut copy = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)
+        .with_path("create", destination)?;
+    let copied = File::open(path)
+        .with_path("open", path)
+        .and_then(|mut source| io::copy(&mut source, &mut copy).with_path("copy to", destination))
+        .and_then(|_| copy.sync_all().with_path("write", destination));
+    if copied.is_err() {
+        let _ = fs::remove_file(destination);
+    }
+    copied?;
+
+    let checked = check_backup(path, destination);
+    if checked.is_err() {
+        let _ = fs::remove_file(destination);
+    }
+    checked
+}
+
+fn check_backup(path: &Path, destination: &Path) -> io::Result<usize> {
+    let size = |path: &Path| {
+        fs::metadata(path)
+            .with_path("read", path)
+            .map(|meta| meta.len())
+    };
+    let count = |path: &Path| {
+        let mut count = 0;
+        scan(path, |_| count += 1, |_| ()).map(|_| count)
+    };
+
+    let expected = count(path)?;
+    let copied = count(destination)?;
+    if size(path)? != size(destination)? || copied != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "backup {} doesn't match the logbook ({copied} of {expected} flights readable)",
+                destination.display()
+            ),
+        ));
+    }
+
+    Ok(copied)
+}
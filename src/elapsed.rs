@@ -0,0 +1,193 @@
+use std::{
+    fmt,
+    num::{ParseFloatError, ParseIntError},
+    str::FromStr,
+};
+
+use chrono::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseElapsedTimeError {
+    #[error(transparent)]
+    Num(#[from] ParseIntError),
+    #[error(transparent)]
+    Float(#[from] ParseFloatError),
+    #[error("elapsed time must be greater than zero")]
+    NonPositive,
+    #[error("elapsed time is too long")]
+    TooLong,
+    #[error("hours, minutes, and seconds can't have a sign")]
+    Signed,
+}
+
+/// A length of time as a pilot would write it.
+///
+/// Displays as hours+minutes; the alternate form (`{:#}`) adds seconds, for those who time
+/// pattern work to the second.
+#[derive(Clone, Copy, Debug)]
+pub struct ElapsedTime {
+    hours: i32,
+    minutes: i32,
+    seconds: i32,
+}
+
+impl ElapsedTime {
+    pub fn into_duration(self) -> Duration {
+        Duration::hours(self.hours as i64)
+            + Duration::minutes(self.minutes as i64)
+            + Duration::seconds(self.seconds as i64)
+    }
+
+    fn from_seconds(total_seconds: i32) -> Self {
+        ElapsedTime {
+            hours: total_seconds / 3600,
+            minutes: total_seconds / 60 % 60,
+            seconds: total_seconds % 60,
+        }
+    }
+}
+
+impl TryFrom<Duration> for ElapsedTime {
+    type Error = ParseElapsedTimeError;
+
+    /// Fails with `TooLong` past what the parser would accept, rather than wrapping around.
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        i32::try_from(duration.num_seconds())
+            .map(ElapsedTime::from_seconds)
+            .map_err(|_| ParseElapsedTimeError::TooLong)
+    }
+}
+
+impl fmt::Display for ElapsedTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}+{:02}+{:02}", self.hours, self.minutes, self.seconds)
+        } else {
+            write!(f, "{}+{:02}", self.hours, self.minutes)
+        }
+    }
+}
+
+/// Renders a length of time as hours+minutes, with the minutes always two digits (`2+03`).
+///
+/// Everything shown to the user should come through here or through `ElapsedTime` itself, so
+/// that a total reads the same wherever it turns up.
+pub fn format_duration(duration: Duration) -> String {
+    // Worked out here rather than through `ElapsedTime`, since a total can run longer than any
+    // one flight.
+    let minutes = duration.num_minutes();
+    format!("{}+{:02}", minutes / 60, minutes % 60)
+}
+
+impl FromStr for ElapsedTime {
+    type Err = ParseElapsedTimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let total_seconds = parse_seconds(s)?;
+        if total_seconds <= 0 {
+            return Err(ParseElapsedTimeError::NonPositive);
+        }
+        let total_seconds =
+            i32::try_from(total_seconds).map_err(|_| ParseElapsedTimeError::TooLong)?;
+        Ok(ElapsedTime::from_seconds(total_seconds))
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<i64, ParseElapsedTimeError> {
+    // Seconds on their own, e.g. "95s".
+    if let Some(seconds) = s.strip_suffix('s') {
+        return unsigned(seconds);
+    }
+
+    // Minutes or seconds past 59 carry, so "2+75" is 3+15.
+    let parts: Vec<_> = s.split(['+', ':']).collect();
+    match parts[..] {
+        [hours, minutes, seconds] => {
+            return Ok(unsigned(hours)? * 3600 + unsigned(minutes)? * 60 + unsigned(seconds)?);
+        }
+        [hours, minutes] => {
+            return Ok((unsigned(hours)? * 60 + unsigned(minutes)?) * 60);
+        }
+        _ => (),
+    }
+
+    // Decimal hours, e.g. "1.5" -- rounded to the nearest minute.
+    if s.contains('.') {
+        let hours: f64 = s.parse()?;
+        return Ok(((hours * 60.0).round() * 60.0) as i64);
+    }
+
+    Ok(unsigned(s)? * 60)
+}
+
+/// Parses one part of a time. A sign is refused outright, since otherwise `2+-5` would quietly
+/// come out as 1+55.
+fn unsigned(s: &str) -> Result<i64, ParseElapsedTimeError> {
+    if s.starts_with(['+', '-']) {
+        return Err(ParseElapsedTimeError::Signed);
+    }
+    Ok(i64::from(s.parse::<u32>()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> String {
+        s.parse::<ElapsedTime>().unwrap().to_string()
+    }
+
+    #[test]
+    fn minutes_past_59_carry() {
+        assert_eq!(parse("0+60"), "1+00");
+        assert_eq!(parse("1+120"), "3+00");
+        assert_eq!(parse("2+75"), "3+15");
+    }
+
+    #[test]
+    fn signs_are_refused() {
+        for time in ["2+-5", "0+-5+400", "-5s", "2:+5", "-90"] {
+            assert!(time.parse::<ElapsedTime>().is_err(), "{time} parsed");
+        }
+    }
+
+    #[test]
+    fn zero_is_refused() {
+        assert!(matches!(
+            "0+00".parse::<ElapsedTime>(),
+            Err(ParseElapsedTimeError::NonPositive)
+        ));
+        assert!("-1.5".parse::<ElapsedTime>().is_err());
+    }
+
+    #[test]
+    fn durations_too_long_to_parse_are_refused() {
+        let longest = Duration::seconds(i32::MAX as i64);
+        assert_eq!(
+            ElapsedTime::try_from(longest).unwrap().into_duration(),
+            longest
+        );
+        assert!(matches!(
+            ElapsedTime::try_from(longest + Duration::seconds(1)),
+            Err(ParseElapsedTimeError::TooLong)
+        ));
+    }
+
+    #[test]
+    fn long_totals_still_format() {
+        assert_eq!(format_duration(Duration::hours(1_000_000)), "1000000+00");
+    }
+
+    #[test]
+    fn decimal_hours_round_to_the_minute() {
+        assert_eq!(parse("1.5"), "1+30");
+        assert_eq!(parse("0.25"), "0+15");
+    }
+
+    #[test]
+    fn colon_minutes_need_not_be_padded() {
+        assert_eq!(parse("2:3"), "2+03");
+        assert_eq!(parse("2:03"), "2+03");
+        assert!("2:".parse::<ElapsedTime>().is_err());
+    }
+}
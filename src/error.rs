@@ -0,0 +1,29 @@
+use std::{io, path::Path};
+
+/// Adds the failed operation and the file involved to an I/O error, so the user sees
+/// `failed to open /path/to/db.json: Permission denied` rather than just `Permission denied`.
+///
+/// The error kind is kept, so callers can still tell a missing file from any other failure.
+pub trait PathContext<T> {
+    fn with_path(self, operation: &str, path: &Path) -> io::Result<T>;
+}
+
+impl<T> PathContext<T> for io::Result<T> {
+    fn with_path(self, operation: &str, path: &Path) -> io::Result<T> {
+        self.map_err(|e| {
+            // The OS error code means nothing to most people.
+            let message = e.to_string();
+            let message = match e.raw_os_error() {
+                Some(code) => message
+                    .strip_suffix(&format!(" (os error {code})"))
+                    .unwrap_or(&message),
+                None => &message,
+            };
+
+            io::Error::new(
+                e.kind(),
+                format!("failed to {operation} {}: {message}", path.display()),
+            )
+        })
+    }
+}
@@ -0,0 +1,238 @@
+use std::io::{self, Write};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{airport, flight::Flight};
+
+pub static CSV_HEADER: &str = "created,waypoints,elapsed_minutes,aircraft,notes";
+
+pub fn write_csv<'a>(
+    mut out: impl Write,
+    flights: impl IntoIterator<Item = &'a Flight>,
+) -> io::Result<()> {
+    writeln!(out, "{CSV_HEADER}")?;
+    for flight in flights {
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            flight.created.to_rfc3339_opts(SecondsFormat::Secs, true),
+            csv_escape(&flight.waypoints.join("-")),
+            flight.elapsed.num_minutes(),
+            csv_escape(flight.aircraft.as_deref().unwrap_or_default()),
+            csv_escape(flight.notes.as_deref().unwrap_or_default()),
+        )?;
+    }
+    out.flush()
+}
+
+/// Quotes a field if it contains anything that would confuse a CSV reader, doubling any quotes
+/// inside it.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+/// Writes a GPX 1.1 document containing the flight's route.
+///
+/// Every waypoint needs known coordinates; we'd rather fail than hand a mapping tool a route with
+/// holes in it.
+pub fn write_gpx(mut out: impl Write, flight: &Flight) -> io::Result<()> {
+    let unknown = airport::unknown_waypoints(&flight.waypoints);
+    if !unknown.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no coordinates for waypoints: {}", unknown.join(", ")),
+        ));
+    }
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<gpx version="1.1" creator="route" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+    writeln!(out, "  <rte>")?;
+    writeln!(
+        out,
+        "    <name>{}</name>",
+        xml_escape(&flight.waypoints.join("-"))
+    )?;
+    for waypoint in &flight.waypoints {
+        let coordinates = airport::lookup(waypoint).unwrap();
+        writeln!(
+            out,
+            r#"    <rtept lat="{}" lon="{}"><name>{}</name></rtept>"#,
+            coordinates.lat,
+            coordinates.lon,
+            xml_escape(waypoint),
+        )?;
+    }
+    writeln!(out, "  </rte>")?;
+    writeln!(out, "</gpx>")?;
+    out.flush()
+}
+
+/// Writes a KML document with a line for each flight and a point for each airport visited.
+///
+/// Flights with unknown waypoints are left out rather than failing the whole export; the number
+/// skipped is returned so the caller can mention it.
+pub fn write_kml<'a>(
+    mut out: impl Write,
+    flights: impl IntoIterator<Item = &'a Flight>,
+) -> io::Result<usize> {
+    let mut skipped = 0;
+    let mut lines = Vec::new();
+    let mut airports: Vec<(&str, airport::Coordinates)> = Vec::new();
+
+    for flight in flights {
+        let Some(coordinates) = flight
+            .waypoints
+            .iter()
+            .map(|waypoint| airport::lookup(waypoint))
+            .collect::<Option<Vec<_>>>()
+        else {
+            skipped += 1;
+            continue;
+        };
+
+        for (waypoint, &coordinates) in flight.waypoints.iter().zip(&coordinates) {
+            if !airports.iter().any(|(ident, _)| ident == waypoint) {
+                airports.push((waypoint, coordinates));
+            }
+        }
+        lines.push((flight, coordinates));
+    }
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+    writeln!(out, "  <Document>")?;
+    writeln!(out, "    <name>route</name>")?;
+    for (flight, coordinates) in lines {
+        // KML wants longitude first, and an altitude we don't have.
+        let coordinates: Vec<_> = coordinates
+            .iter()
+            .map(|c| format!("{},{},0", c.lon, c.lat))
+            .collect();
+        writeln!(out, "    <Placemark>")?;
+        writeln!(
+            out,
+            "      <name>{}</name>",
+            xml_escape(&flight.waypoints.join("-"))
+        )?;
+        writeln!(
+            out,
+            "      <TimeStamp><when>{}</when></TimeStamp>",
+            flight.created.to_rfc3339_opts(SecondsFormat::Secs, true)
+        )?;
+        writeln!(out, "      <LineString><tessellate>1</tessellate>")?;
+        writeln!(
+            out,
+            "        <coordinates>{}</coordinates>",
+            coordinates.join(" ")
+        )?;
+        writeln!(out, "      </LineString>")?;
+        writeln!(out, "    </Placemark>")?;
+    }
+    for (ident, coordinates) in airports {
+        writeln!(
+            out,
+            "    <Placemark><name>{}</name><Point><coordinates>{},{},0</coordinates></Point></Placemark>",
+            xml_escape(ident),
+            coordinates.lon,
+            coordinates.lat,
+        )?;
+    }
+    writeln!(out, "  </Document>")?;
+    writeln!(out, "</kml>")?;
+    out.flush()?;
+
+    Ok(skipped)
+}
+
+/// Writes an iCalendar file with an event for each flight, running from when it was logged for
+/// as long as it lasted.
+pub fn write_ics<'a>(
+    mut out: impl Write,
+    flights: impl IntoIterator<Item = &'a Flight>,
+    now: DateTime<Utc>,
+) -> io::Result<()> {
+    let stamp = ics_time(now);
+
+    write_ics_line(&mut out, "BEGIN:VCALENDAR")?;
+    write_ics_line(&mut out, "VERSION:2.0")?;
+    write_ics_line(&mut out, "PRODID:-//Hack Commons//route//EN")?;
+    for flight in flights {
+        let route = flight.waypoints.join(" - ");
+        write_ics_line(&mut out, "BEGIN:VEVENT")?;
+        write_ics_line(&mut out, &format!("UID:{}@route", flight.id))?;
+        write_ics_line(&mut out, &format!("DTSTAMP:{stamp}"))?;
+        write_ics_line(&mut out, &format!("DTSTART:{}", ics_time(flight.created)))?;
+        write_ics_line(
+            &mut out,
+            &format!("DTEND:{}", ics_time(flight.created + flight.elapsed)),
+        )?;
+        write_ics_line(&mut out, &format!("SUMMARY:{}", ics_escape(&route)))?;
+        if let Some(notes) = &flight.notes {
+            write_ics_line(&mut out, &format!("DESCRIPTION:{}", ics_escape(notes)))?;
+        }
+        write_ics_line(&mut out, "END:VEVENT")?;
+    }
+    write_ics_line(&mut out, "END:VCALENDAR")?;
+    out.flush()
+}
+
+fn ics_time(instant: DateTime<Utc>) -> String {
+    instant.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn ics_escape(text: &str) -> String {
+    let mut buf = String::with_capacity(text.len());
+    for u in text.chars() {
+        match u {
+            '\\' => buf.push_str("\\\\"),
+            ';' => buf.push_str("\\;"),
+            ',' => buf.push_str("\\,"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => (),
+            u => buf.push(u),
+        }
+    }
+    buf
+}
+
+/// Writes a content line, folded so no physical line runs past 75 bytes, as RFC 5545 requires.
+/// Continuation lines start with a space, and multibyte characters are never split.
+fn write_ics_line(out: &mut impl Write, line: &str) -> io::Result<()> {
+    let mut rest = line;
+    let mut limit = 75;
+    while rest.len() > limit {
+        let mut split = limit;
+        while !rest.is_char_boundary(split) {
+            split -= 1;
+        }
+        out.write_all(&rest.as_bytes()[..split])?;
+        out.write_all(b"\r\n ")?;
+        rest = &rest[split..];
+        // The leading space counts toward the next line's length.
+        limit = 74;
+    }
+    out.write_all(rest.as_bytes())?;
+    out.write_all(b"\r\n")
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut buf = String::with_capacity(text.len());
+    for u in text.chars() {
+        match u {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            '\'' => buf.push_str("&apos;"),
+            u => buf.push(u),
+        }
+    }
+    buf
+}
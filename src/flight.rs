@@ -0,0 +1,595 @@
+use std::{io, iter};
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde::{de::Error as _, Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::{self, serde_as};
+use ulid::Ulid;
+
+use crate::{
+    airport::{self, Coordinates},
+    elapsed::ElapsedTime,
+};
+
+/// The schema version written with every new record.
+///
+/// Version 1 is the original shape: created, waypoints, elapsed, plus optional fields such as
+/// aircraft and notes. Records written before versioning carry no version field and are treated
+/// as version 1. Version 2 adds a unique id to every record.
+pub const CURRENT_VERSION: u32 = 2;
+
+const CROSS_COUNTRY_NM: f64 = 50.0;
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Flight {
+    #[serde(default = "implicit_version")]
+    pub version: u32,
+    /// A ULID, which sorts by creation time and doesn't change when other flights come and go.
+    pub id: String,
+    pub created: DateTime<Utc>,
+    pub waypoints: Vec<String>,
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub elapsed: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depart: Option<NaiveTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrive: Option<NaiveTime>,
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub night: Option<Duration>,
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instrument: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aircraft: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub landings: Option<u32>,
+    /// Fuel burned, in whatever units the pilot buys it in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuel: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub simulator: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+/// Every field a record may have, for turning away records from elsewhere that have more.
+pub const FIELDS: &[&str] = &[
+    "version",
+    "id",
+    "created",
+    "waypoints",
+    "elapsed",
+    "depart",
+    "arrive",
+    "night",
+    "instrument",
+    "aircraft",
+    "landings",
+    "fuel",
+    "tags",
+    "simulator",
+    "notes",
+];
+
+impl Flight {
+    pub fn new<T: AsRef<str>>(
+        origin: impl AsRef<str>,
+        waypoints: impl IntoIterator<Item = T>,
+        elapsed: ElapsedTime,
+    ) -> Self {
+        let waypoints = iter::once(origin.as_ref().trim().to_ascii_uppercase()).chain(
+            waypoints
+                .into_iter()
+                .map(|wpt| wpt.as_ref().trim().to_ascii_uppercase()),
+        );
+
+        Self {
+            version: CURRENT_VERSION,
+            id: Ulid::generate().to_string(),
+            created: Utc::now(),
+            waypoints: waypoints.collect(),
+            elapsed: elapsed.into_duration(),
+            depart: None,
+            arrive: None,
+            night: None,
+            instrument: None,
+            aircraft: None,
+            landings: None,
+            fuel: None,
+            tags: Vec::new(),
+            simulator: false,
+            notes: None,
+        }
+    }
+
+    /// The trip home: the same route flown backwards, in the same aircraft, on the same day.
+    ///
+    /// It's taken to start when the outbound leg ends, so the two never overlap and always sort
+    /// in the order they were flown. Times, notes, and anything else specific to the outbound leg
+    /// are left behind.
+    pub fn reversed(&self, elapsed: ElapsedTime) -> Self {
+        let mut waypoints = self.waypoints.iter().rev();
+        let origin = waypoints.next().map(String::as_str).unwrap_or_default();
+
+        let mut flight = Flight::new(origin, waypoints, elapsed);
+        flight.created = self.created + self.elapsed;
+        flight.aircraft = self.aircraft.clone();
+        flight.landings = self.landings;
+        flight.tags = self.tags.clone();
+        flight.simulator = self.simulator;
+        flight
+    }
+
+    pub fn add_aircraft(&mut self, aircraft: impl AsRef<str>) {
+        self.aircraft = Some(aircraft.as_ref().to_ascii_uppercase())
+    }
+
+    pub fn add_tags<T: AsRef<str>>(&mut self, tags: impl IntoIterator<Item = T>) {
+        for tag in tags {
+            let tag = tag.as_ref().trim().to_lowercase();
+            if !tag.is_empty() && !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim().to_lowercase();
+        self.tags.contains(&tag)
+    }
+
+    pub fn add_notes(&mut self, notes: impl Into<String>) {
+        self.notes = Some(notes.into())
+    }
+
+    /// Whether the flight ends more than 50 nm (straight line) from where it started, which is how
+    /// the FAA defines cross-country time. Unknown if either end has no known coordinates.
+    pub fn is_cross_country(&self) -> Option<bool> {
+        let origin = airport::lookup(self.waypoints.first()?)?;
+        let destination = airport::lookup(self.waypoints.last()?)?;
+        Some(origin.distance_to(destination) > CROSS_COUNTRY_NM)
+    }
+
+    /// Average groundspeed in knots, taking the route as flown in a straight line between
+    /// waypoints. Unknown if any waypoint has no known coordinates, and meaningless for a flight
+    /// that ended where it started.
+    pub fn groundspeed(&self) -> Option<f64> {
+        let distance = airport::route_distance(&self.waypoints)?;
+        let hours = self.elapsed.num_minutes() as f64 / 60.0;
+        (hours > 0.0 && distance > 0.0).then(|| distance / hours)
+    }
+
+    /// Fuel burned per hour.
+    pub fn burn_rate(&self) -> Option<f64> {
+        let hours = self.elapsed.num_minutes() as f64 / 60.0;
+        self.fuel.filter(|_| hours > 0.0).map(|fuel| fuel / hours)
+    }
+
+    /// Nautical miles covered per unit of fuel burned.
+    pub fn fuel_economy(&self) -> Option<f64> {
+        let distance = airport::route_distance(&self.waypoints)?;
+        self.fuel.map(|fuel| distance / fuel)
+    }
+
+    /// Flights logged without a landing count are assumed to have made one landing.
+    pub fn landing_count(&self) -> u32 {
+        self.landings.unwrap_or(1)
+    }
+}
+
+/// Tidies up the route as typed: stray whitespace and commas are trimmed, and identifiers are
+/// uppercased. Anything left empty is an error rather than a waypoint.
+pub fn normalize_route(origin: &str, waypoints: &[String]) -> io::Result<Vec<String>> {
+    iter::once(origin)
+        .chain(waypoints.iter().map(String::as_str))
+        .map(|waypoint| {
+            let waypoint = waypoint.trim_matches(|u: char| u.is_whitespace() || u == ',');
+            if waypoint.is_empty() {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "empty waypoint in route; check for stray spaces or commas",
+                ))
+            } else {
+                Ok(waypoint.to_ascii_uppercase())
+            }
+        })
+        .collect()
+}
+
+/// Finds a waypoint given twice in a row, which is almost always a paste error. Coming back
+/// through a waypoint later in the route is fine; that's just where you went.
+pub fn repeated_waypoint(route: &[String]) -> Option<&str> {
+    // An out-and-back to the same field is a local flight, not a repeat.
+    if route.len() == 2 {
+        return None;
+    }
+
+    route
+        .windows(2)
+        .find(|pair| pair[0] == pair[1])
+        .map(|pair| pair[0].as_str())
+}
+
+/// Separates the elapsed time from the waypoints it trails, checking it against the departure and
+/// arrival times if those were given.
+pub fn split_elapsed(
+    waypoints: &[String],
+    depart: Option<NaiveTime>,
+    arrive: Option<NaiveTime>,
+) -> io::Result<(&[String], ElapsedTime)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    let block = match (depart, arrive) {
+        (Some(depart), Some(arrive)) => Some(
+            block_time(depart, arrive)
+                .ok_or_else(|| invalid(String::from("departure and arrival times are the same")))?,
+        ),
+        _ => None,
+    };
+
+    // Without clock times the elapsed time is mandatory, so the last argument had better be one.
+    // With them, it's only an elapsed time if it reads as one.
+    let (waypoints, elapsed) = match waypoints.split_last() {
+        Some((last, rest)) if !rest.is_empty() => match last.parse::<ElapsedTime>() {
+            Ok(elapsed) => (rest, Some(elapsed)),
+            Err(_) if block.is_some() => (waypoints, None),
+            Err(e) => return Err(invalid(format!("invalid elapsed time '{last}': {e}"))),
+        },
+        _ if block.is_some() => (waypoints, None),
+        _ => {
+            return Err(invalid(String::from(
+                "expected a destination followed by the elapsed time",
+            )))
+        }
+    };
+
+    match (elapsed, block) {
+        (Some(elapsed), Some(block)) => {
+            let difference = (elapsed.into_duration() - block.into_duration()).abs();
+            if difference > Duration::minutes(1) {
+                return Err(invalid(format!(
+                    "elapsed time ({elapsed}) disagrees with departure and arrival times ({block})"
+                )));
+            }
+            Ok((waypoints, elapsed))
+        }
+        (Some(elapsed), None) | (None, Some(elapsed)) => Ok((waypoints, elapsed)),
+        (None, None) => unreachable!(),
+    }
+}
+
+/// The time between departure and arrival, assuming a flight that lands before the clock comes
+/// back around to its departure time.
+pub fn block_time(depart: NaiveTime, arrive: NaiveTime) -> Option<ElapsedTime> {
+    let mut duration = arrive - depart;
+    if duration < Duration::zero() {
+        duration += Duration::days(1);
+    }
+    ElapsedTime::try_from(duration)
+        .ok()
+        .filter(|_| duration > Duration::zero())
+}
+
+/// Night and instrument time are portions of the flight, so neither can exceed the whole.
+pub fn validate_sub_duration(
+    name: &str,
+    duration: Option<ElapsedTime>,
+    elapsed: ElapsedTime,
+) -> io::Result<()> {
+    match duration {
+        Some(duration) if duration.into_duration() > elapsed.into_duration() => {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name} time ({duration}) exceeds elapsed time ({elapsed})"),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that every waypoint is an identifier in the airport table or `LAT,LON` coordinates,
+/// suggesting near misses for one that isn't.
+pub fn validate_waypoints<'a>(waypoints: impl IntoIterator<Item = &'a String>) -> io::Result<()> {
+    for waypoint in waypoints {
+        if Coordinates::parse(waypoint).is_some() {
+            continue;
+        }
+
+        if !is_plausible_identifier(waypoint) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid waypoint '{waypoint}': expected a 3 or 4 character identifier or \
+                     LAT,LON coordinates"
+                ),
+            ));
+        }
+
+        if airport::lookup(waypoint).is_none() {
+            let waypoint = waypoint.to_ascii_uppercase();
+            let suggestions = airport::suggest(&waypoint, 3);
+            let message = if suggestions.is_empty() {
+                format!("unknown waypoint '{waypoint}'; use --no-validate to log it anyway")
+            } else {
+                format!(
+                    "unknown waypoint '{waypoint}'; did you mean {}?",
+                    suggestions.join(", ")
+                )
+            };
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+    }
+    Ok(())
+}
+
+/// ICAO identifiers are four characters and IATA identifiers are three; either way, they're
+/// alphanumeric.
+fn is_plausible_identifier(waypoint: &str) -> bool {
+    (3..=4).contains(&waypoint.len()) && waypoint.bytes().all(|u| u.is_ascii_alphanumeric())
+}
+
+/// How far back to look for a flight that might be logged twice.
+const DUPLICATE_LOOKBACK: usize = 5;
+
+/// Finds a flight among the last few entries that matches this one -- same route, same elapsed
+/// time, within an hour -- since that's almost always the same flight submitted twice.
+pub fn find_duplicate<'a>(
+    flights: &'a [Flight],
+    route: &[String],
+    elapsed: ElapsedTime,
+    created: DateTime<Utc>,
+) -> Option<&'a Flight> {
+    flights
+        .iter()
+        .rev()
+        .take(DUPLICATE_LOOKBACK)
+        .find(|flight| {
+            flight.elapsed == elapsed.into_duration()
+                && (flight.created - created).abs() < Duration::hours(1)
+                && flight.waypoints.len() == route.len()
+                && iter::zip(&flight.waypoints, route).all(|(a, b)| a.eq_ignore_ascii_case(b))
+        })
+}
+
+/// Finds a flight by its id, or by a prefix of one that no other flight shares.
+pub fn find_by_id(flights: &[Flight], id: &str) -> io::Result<usize> {
+    // ULIDs are written in uppercase, but Crockford's base32 doesn't care.
+    let prefix = id.to_ascii_uppercase();
+    let mut matches = flights
+        .iter()
+        .enumerate()
+        .filter(|(_, flight)| flight.id.starts_with(&prefix))
+        .map(|(idx, _)| idx);
+
+    match (matches.next(), matches.next()) {
+        (Some(idx), None) => Ok(idx),
+        (None, _) => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no flight with id {id}"),
+        )),
+        (Some(_), Some(_)) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} flights have ids starting with {id}; use more of the id",
+                matches.count() + 2
+            ),
+        )),
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Makes up an id for a record from before ids existed.
+///
+/// A record keeps the id it's given here only once the log is next rewritten, so until then it has
+/// to come out the same on every read. Rather than random bits, then, it takes its time from the
+/// flight's creation and the rest from a hash of the record itself.
+fn legacy_id(record: &Value) -> serde_json::Result<String> {
+    let created: DateTime<Utc> = serde_json::from_value(record["created"].clone())?;
+
+    // FNV-1a, twice over with different seeds for enough bits. Unlike std's hashers, it's
+    // guaranteed not to change between releases.
+    let text = record.to_string();
+    let fnv = |seed: u64| {
+        text.bytes().fold(seed, |hash, u| {
+            (hash ^ u as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    };
+    let random = (fnv(0xcbf2_9ce4_8422_2325) as u128) << 64 | fnv(0x6c62_272e_07bb_0142) as u128;
+
+    Ok(Ulid::from_parts(created.timestamp_millis() as u64, random).to_string())
+}
+
+fn implicit_version() -> u32 {
+    1
+}
+
+/// Upgrades a stored record of any known version to the current shape.
+///
+/// Each schema change should add a step here that rewrites the raw record from version N to
+/// N + 1; new optional fields need no step, since they simply deserialize as `None`. Records
+/// from a newer version of the tool are rejected rather than guessed at.
+pub fn migrate(mut record: Value) -> serde_json::Result<Flight> {
+    let version = match record.get("version") {
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| serde_json::Error::custom("invalid schema version"))?,
+        None => implicit_version() as u64,
+    };
+
+    if version > CURRENT_VERSION as u64 {
+        return Err(serde_json::Error::custom(format!(
+            "unsupported schema version {version}"
+        )));
+    }
+
+    if version < 2 {
+        let id = legacy_id(&record)?;
+        record["id"] = Value::String(id);
+    }
+
+    let mut flight: Flight = serde_json::from_value(record)?;
+    flight.version = CURRENT_VERSION;
+    Ok(flight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(waypoints: &[&str]) -> Vec<String> {
+        waypoints
+            .iter()
+            .map(|waypoint| waypoint.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn back_to_back_repeat_is_caught() {
+        assert_eq!(repeated_waypoint(&route(&["A", "A", "B"])), Some("A"));
+    }
+
+    #[test]
+    fn coming_back_later_is_fine() {
+        assert_eq!(repeated_waypoint(&route(&["A", "B", "A"])), None);
+    }
+
+    #[test]
+    fn out_and_back_to_one_field_is_fine() {
+        assert_eq!(repeated_waypoint(&route(&["A", "A"])), None);
+    }
+
+    #[test]
+    fn single_waypoint_has_nothing_to_repeat() {
+        assert_eq!(repeated_waypoint(&route(&["A"])), None);
+    }
+
+    #[test]
+    fn route_is_trimmed_and_uppercased() {
+        let normalized = normalize_route(" ksea,", &route(&["kpdx "])).unwrap();
+        assert_eq!(normalized, ["KSEA", "KPDX"]);
+        assert!(normalize_route("KSEA", &route(&[" , "])).is_err());
+    }
+
+    fn time(time: &str) -> Option<NaiveTime> {
+        Some(time.parse().unwrap())
+    }
+
+    fn elapsed(time: &str) -> ElapsedTime {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn elapsed_time_trails_the_route() {
+        let args = route(&["KPDX", "1+10"]);
+        let (waypoints, elapsed) = split_elapsed(&args, None, None).unwrap();
+        assert_eq!(waypoints, ["KPDX"]);
+        assert_eq!(elapsed.to_string(), "1+10");
+
+        assert!(split_elapsed(&route(&["KPDX"]), None, None).is_err());
+        assert!(split_elapsed(&route(&["KPDX", "KBFI"]), None, None).is_err());
+    }
+
+    #[test]
+    fn clock_times_stand_in_for_elapsed_time() {
+        let waypoints = route(&["KPDX"]);
+        let (rest, elapsed) = split_elapsed(&waypoints, time("14:05"), time("15:20")).unwrap();
+        assert_eq!(rest, ["KPDX"]);
+        assert_eq!(elapsed.to_string(), "1+15");
+    }
+
+    #[test]
+    fn clock_times_and_elapsed_time_may_differ_by_a_minute() {
+        let (depart, arrive) = (time("14:05"), time("15:20"));
+        assert!(split_elapsed(&route(&["KPDX", "1+16"]), depart, arrive).is_ok());
+        assert!(split_elapsed(&route(&["KPDX", "1+14"]), depart, arrive).is_ok());
+        assert!(split_elapsed(&route(&["KPDX", "1+17"]), depart, arrive).is_err());
+    }
+
+    #[test]
+    fn block_time_wraps_past_midnight() {
+        let block = block_time(time("23:30").unwrap(), time("00:45").unwrap()).unwrap();
+        assert_eq!(block.to_string(), "1+15");
+        assert!(block_time(time("09:00").unwrap(), time("09:00").unwrap()).is_none());
+    }
+
+    #[test]
+    fn sub_durations_fit_inside_the_flight() {
+        let total = elapsed("1+30");
+        assert!(validate_sub_duration("night", Some(elapsed("1+30")), total).is_ok());
+        assert!(validate_sub_duration("night", None, total).is_ok());
+        assert!(validate_sub_duration("night", Some(elapsed("1+31")), total).is_err());
+    }
+
+    #[test]
+    fn waypoints_are_checked_against_the_table() {
+        assert!(validate_waypoints(&route(&["KSEA", "pdx", "47.45,-122.31"])).is_ok());
+        assert!(validate_waypoints(&route(&["KS-A"])).is_err());
+        assert!(validate_waypoints(&route(&["KSEAX"])).is_err());
+        assert!(validate_waypoints(&route(&["KQQQ"])).is_err());
+    }
+
+    #[test]
+    fn identifiers_are_three_or_four_alphanumerics() {
+        assert!(is_plausible_identifier("SEA"));
+        assert!(is_plausible_identifier("0S9"));
+        assert!(!is_plausible_identifier("KS"));
+        assert!(!is_plausible_identifier("KS-A"));
+    }
+
+    #[test]
+    fn duplicates_are_recent_and_alike() {
+        let mut logged = Flight::new("KSEA", ["KPDX"], elapsed("1+10"));
+        logged.created = "2024-05-01T12:00:00Z".parse().unwrap();
+        let flights = [logged];
+        let soon = "2024-05-01T12:59:00Z".parse().unwrap();
+        let later = "2024-05-01T13:00:00Z".parse().unwrap();
+
+        let ksea_kpdx = route(&["ksea", "kpdx"]);
+        assert!(find_duplicate(&flights, &ksea_kpdx, elapsed("1+10"), soon).is_some());
+        assert!(find_duplicate(&flights, &ksea_kpdx, elapsed("1+10"), later).is_none());
+        assert!(find_duplicate(&flights, &ksea_kpdx, elapsed("1+11"), soon).is_none());
+        let kpdx_ksea = route(&["KPDX", "KSEA"]);
+        assert!(find_duplicate(&flights, &kpdx_ksea, elapsed("1+10"), soon).is_none());
+    }
+
+    #[test]
+    fn only_the_last_few_flights_count_as_duplicates() {
+        let created = "2024-05-01T12:00:00Z".parse().unwrap();
+        let mut flights = vec![Flight::new("KSEA", ["KPDX"], elapsed("1+10"))];
+        flights
+            .extend((0..DUPLICATE_LOOKBACK).map(|_| Flight::new("KBFI", ["KBFI"], elapsed("30"))));
+        for flight in &mut flights {
+            flight.created = created;
+        }
+
+        let ksea_kpdx = route(&["KSEA", "KPDX"]);
+        assert!(find_duplicate(&flights, &ksea_kpdx, elapsed("1+10"), created).is_none());
+    }
+
+    #[test]
+    fn ids_match_by_unique_prefix() {
+        let mut flights = [
+            Flight::new("KSEA", ["KPDX"], elapsed("1+10")),
+            Flight::new("KPDX", ["KSEA"], elapsed("1+10")),
+        ];
+        flights[0].id = String::from("01HX0000000000000000000000");
+        flights[1].id = String::from("01HY0000000000000000000000");
+
+        assert_eq!(find_by_id(&flights, "01hy").unwrap(), 1);
+        assert_eq!(
+            find_by_id(&flights, "01HX0000000000000000000000").unwrap(),
+            0
+        );
+        let ambiguous = find_by_id(&flights, "01H").unwrap_err();
+        assert_eq!(ambiguous.kind(), io::ErrorKind::InvalidInput);
+        let missing = find_by_id(&flights, "01J").unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+    }
+}
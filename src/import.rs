@@ -0,0 +1,136 @@
+use std::{io, mem};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{elapsed::ElapsedTime, export::CSV_HEADER, flight::Flight};
+
+/// Flights read from an import, along with the lines of any rows that couldn't be used.
+pub struct Imported {
+    pub flights: Vec<Flight>,
+    /// The line each flight came from.
+    pub lines: Vec<usize>,
+    pub skipped: Vec<usize>,
+}
+
+/// Reads flights from CSV in the shape `export` writes.
+///
+/// A header that doesn't match is fatal, since the columns would mean something else entirely;
+/// a bad row only costs that row.
+pub fn read_csv(text: &str) -> io::Result<Imported> {
+    let mut records = parse_csv(text).into_iter();
+
+    let header = records.next().map(|(_, fields)| fields.join(","));
+    if header.as_deref() != Some(CSV_HEADER) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected a CSV header of '{CSV_HEADER}'"),
+        ));
+    }
+
+    let mut imported = Imported {
+        flights: Vec::new(),
+        lines: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for (line, fields) in records {
+        match flight_from_row(&fields) {
+            Some(flight) => {
+                imported.flights.push(flight);
+                imported.lines.push(line);
+            }
+            None => imported.skipped.push(line),
+        }
+    }
+    Ok(imported)
+}
+
+fn flight_from_row(fields: &[String]) -> Option<Flight> {
+    let [created, waypoints, elapsed, aircraft, notes] = fields else {
+        return None;
+    };
+
+    let created: DateTime<Utc> = DateTime::parse_from_rfc3339(created).ok()?.into();
+    let waypoints: Vec<_> = split_route(waypoints).into_iter().map(str::trim).collect();
+    if waypoints.len() < 2 || waypoints.iter().any(|waypoint| waypoint.is_empty()) {
+        return None;
+    }
+    let minutes: i64 = elapsed.trim().parse().ok()?;
+    if minutes <= 0 {
+        return None;
+    }
+
+    let elapsed = ElapsedTime::try_from(Duration::try_minutes(minutes)?).ok()?;
+    let mut flight = Flight::new(waypoints[0], &waypoints[1..], elapsed);
+    flight.created = created;
+    if !aircraft.is_empty() {
+        flight.add_aircraft(aircraft);
+    }
+    if !notes.is_empty() {
+        flight.add_notes(notes.as_str());
+    }
+    Some(flight)
+}
+
+/// Splits a route on the `-`s between waypoints, leaving alone the minus signs in any written as
+/// coordinates, e.g. `KSEA-47.45,-122.31--33.9,151.2`.
+fn split_route(route: &str) -> Vec<&str> {
+    let mut waypoints = Vec::new();
+    let mut start = 0;
+    for (idx, u) in route.char_indices() {
+        // A dash opening a waypoint, or right after the comma in one, is a sign.
+        let is_sign = idx == start || route[..idx].ends_with(',');
+        if u == '-' && !is_sign {
+            waypoints.push(&route[start..idx]);
+            start = idx + 1;
+        }
+    }
+    waypoints.push(&route[start..]);
+    waypoints
+}
+
+/// Splits CSV text into records, each tagged with the line it starts on.
+///
+/// Quoted fields may contain commas, doubled quotes, and line breaks. Blank lines are skipped.
+fn parse_csv(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+
+    let mut chars = text.chars().peekable();
+    while let Some(u) = chars.next() {
+        match u {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => (),
+            '\n' if !quoted => {
+                fields.push(mem::take(&mut field));
+                if fields.len() > 1 || !fields[0].is_empty() {
+                    records.push((start, mem::take(&mut fields)));
+                }
+                fields.clear();
+                line += 1;
+                start = line;
+            }
+            u => {
+                if u == '\n' {
+                    line += 1;
+                }
+                field.push(u);
+            }
+        }
+    }
+
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((start, fields));
+    }
+
+    records
+}
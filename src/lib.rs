@@ -0,0 +1,22 @@
+//! The logbook itself: flights, how they're stored, and what can be worked out from them.
+//!
+//! The `route` binary is the command line over all this. Argument parsing and output live there;
+//! apart from the odd warning on stderr (and `notes`, which opens an editor), nothing here talks
+//! to the user.
+
+pub mod airport;
+pub mod batch;
+pub mod config;
+pub mod date;
+pub mod db;
+pub mod elapsed;
+pub mod error;
+pub mod export;
+pub mod flight;
+pub mod import;
+pub mod night;
+pub mod notes;
+pub mod sketch;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
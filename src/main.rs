@@ -1,191 +1,1997 @@
 use std::{
-    borrow::Cow,
-    env, fmt,
-    fs::{self, OpenOptions},
-    io::{self, Write},
-    iter,
-    num::ParseIntError,
-    path::PathBuf,
-    process::{self, Command},
-    str::FromStr,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Write},
+    iter, mem,
+    path::{self, Path, PathBuf},
+    process,
 };
 
-use chrono::{Duration, DateTime, Utc};
-use clap::Parser;
-use directories::ProjectDirs;
-use serde::Serialize;
-use serde_with::{self, serde_as};
+use args::{
+    AddArgs, ArchiveArgs, Args, BackupArgs, BatchArgs, BatchLine, Command, ConfigCommand,
+    DeleteArgs, EditArgs, ExportArgs, ExportFormat, ImportArgs, ImportFormat, ListArgs, ListFormat,
+    MergeArgs, ReportArgs, SortArgs, StatsArgs,
+};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, SubsecRound, TimeZone, Utc};
+use clap::{CommandFactory, Parser};
+use route::{
+    airport::{self, DistanceUnit},
+    batch,
+    config::{self, Config, FuelUnit},
+    date::DateArg,
+    db::{self, LockMode},
+    elapsed::{self, ElapsedTime},
+    error::PathContext,
+    export,
+    flight::{self, Flight},
+    import, night, notes, sketch,
+    stats::{self, GroupBy, Totals},
+};
+use ulid::Ulid;
 
-static EDITOR: &str = "hx";
+mod args;
+mod table;
 
-#[derive(Debug, thiserror::Error)]
-enum ParseElapsedTimeError {
-    #[error(transparent)]
-    Num(#[from] ParseIntError),
+fn main() {
+    match run(&Args::parse()) {
+        // Output piped into something like `head` that stopped reading; it has what it wanted.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+        Ok(()) => (),
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
-struct ElapsedTime {
-    hours: i32,
-    minutes: i32,
+/// Settings shared by every subcommand.
+struct Context {
+    path: PathBuf,
+    logbook: Option<String>,
+    utc: bool,
+    dry_run: bool,
+    quiet: u8,
+    precise: bool,
+    no_color: bool,
+    units: DistanceUnit,
+    fuel_units: FuelUnit,
+    config: Config,
 }
 
-impl ElapsedTime {
-    fn into_duration(self) -> Duration {
-        Duration::hours(self.hours as i64) + Duration::minutes(self.minutes as i64)
+impl Context {
+    /// Whether to leave out confirmations, counts, and warnings.
+    fn quiet(&self) -> bool {
+        self.quiet > 0
+    }
+
+    /// Whether to warn about unreadable lines in a logbook, which takes `-qq` to turn off.
+    fn warn_unreadable(&self) -> bool {
+        self.quiet < 2
     }
 }
 
-impl fmt::Display for ElapsedTime {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}+{}", self.hours, self.minutes)
+fn run(args: &Args) -> io::Result<()> {
+    // Completions don't touch the logbook, so there's no reason to go find it.
+    if let Some(Command::Completions { shell }) = args.command {
+        clap_complete::generate(shell, &mut Args::command(), "route", &mut io::stdout());
+        return Ok(());
+    }
+
+    if let Some(Command::Config {
+        command: ConfigCommand::Path,
+    }) = args.command
+    {
+        println!("{}", config::config_path()?.display());
+        return Ok(());
+    }
+
+    let config = Config::load(&config::config_path()?)?;
+
+    let context = Context {
+        path: db::get_file_path(
+            args.data_dir.as_deref(),
+            config.data_dir.as_deref(),
+            args.logbook.as_deref(),
+        )?,
+        logbook: args.logbook.clone(),
+        utc: args.utc,
+        dry_run: args.dry_run,
+        quiet: args.quiet,
+        precise: args.precise,
+        no_color: args.no_color,
+        units: args.units.or(config.units).unwrap_or_default(),
+        fuel_units: config.fuel_units.unwrap_or_default(),
+        config,
+    };
+
+    match &args.command {
+        Some(Command::Add(args)) => add(args, &context),
+        Some(Command::List(args)) => list(args, &context),
+        Some(Command::Stats(args)) => stats(args, &context),
+        Some(Command::Currency) => currency(&context),
+        Some(Command::Report(args)) => report(args, &context),
+        Some(Command::Delete(args)) => delete(args, &context),
+        Some(Command::Last) => last(&context),
+        Some(Command::Edit(args)) => edit(args, &context),
+        Some(Command::Note) => note(&context),
+        Some(Command::Export(args)) => export(args, &context),
+        Some(Command::Import(args)) => import(args, &context),
+        Some(Command::Batch(args)) => batch(args, &context),
+        Some(Command::Merge(args)) => merge(args, &context),
+        Some(Command::Sort(args)) => sort(args, &context),
+        Some(Command::Backup(args)) => backup(args, &context),
+        Some(Command::Archive(args)) => archive(args, &context),
+        Some(Command::Verify) => verify(&context),
+        #[cfg(feature = "sqlite")]
+        Some(Command::MigrateToSqlite) => migrate_to_sqlite(&context),
+        Some(Command::Logbooks) => logbooks(&context),
+        Some(Command::Where) => {
+            println!("{}", path::absolute(&context.path)?.display());
+            Ok(())
+        }
+        Some(Command::Completions { .. } | Command::Config { .. }) => unreachable!(),
+
+        // Clap refuses to parse an empty command line, so if there's no subcommand we know the
+        // add arguments are present.
+        None => add(args.add.as_ref().unwrap(), &context),
     }
 }
 
-impl FromStr for ElapsedTime {
-    type Err = ParseElapsedTimeError;
+fn add(args: &AddArgs, context: &Context) -> io::Result<()> {
+    if let Some(json) = &args.from_json {
+        return add_json(json, args, context);
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.split_once('+') {
-            Some((hours, minutes)) => Ok(ElapsedTime {
-                hours: hours.parse()?,
-                minutes: minutes.parse()?,
-            }),
+    // Validate before asking for notes; nobody wants to write a novel only to have it thrown out
+    // over a typo.
+
+    let (route, elapsed, created) = validate_add(args, context)?;
+
+    if !args.force {
+        let flights = {
+            let _lock = db::lock(&context.path, LockMode::Shared)?;
+            db::read_flights(&context.path, context.warn_unreadable())?
+        };
+        check_duplicate(
+            &flights,
+            &route,
+            elapsed,
+            created.unwrap_or_else(Utc::now),
+            context,
+        )?;
+    }
+
+    let night = match args.night {
+        Some(night) => Some(night.into_duration()),
+        None if args.infer_night => infer_night(args, &route, elapsed, created, context)?,
+        None => None,
+    };
+
+    // First off, we need to construct a writable flight model. We don't have a readable one just
+    // yet, but that's... fine. I think. Whatever.
+
+    // A dry run is for checking input, so it doesn't pop open an editor.
+    let notes = match args.notes.as_slice() {
+        [dash] if dash == "-" => notes::read_from_stdin()?,
+        [] => match &args.notes_file {
+            Some(path) => notes::read_from_path(path)?,
+            None if context.dry_run => String::new(),
             None => {
-                let total_minutes: i32 = s.parse()?;
-                let hours = total_minutes / 60;
-                let minutes = total_minutes % 60;
-                Ok(ElapsedTime { hours, minutes })
+                let path = args
+                    .template
+                    .as_ref()
+                    .or(context.config.note_template.as_ref());
+                let template = match path {
+                    Some(path) => Some(fs::read_to_string(path).with_path("read", path)?),
+                    None => None,
+                };
+                notes::read_new(context.config.editor.as_deref(), template.as_deref())?
             }
+        },
+        notes if notes.iter().any(|note| note == "-") => return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--notes - reads the whole note from stdin, so it can't be combined with other notes",
+        )),
+        notes => notes::from_args(notes),
+    };
+    check_note_size(&notes, args.force, context)?;
+
+    let (flight, return_flight) =
+        build_flight(args, context, &route, elapsed, created, night, notes);
+
+    // Next, we need to store the flight model in a database. I use the term loosely. At present,
+    // the database will be line-delimited json.
+
+    if context.dry_run {
+        for flight in iter::once(&flight).chain(&return_flight) {
+            println!("{}", serde_json::to_string(flight)?);
         }
+        return Ok(());
     }
+
+    let out_of_order = {
+        let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+
+        // Only a backdated flight can land out of order, so only then is it worth reading the log.
+        let out_of_order = created.is_some()
+            && db::read_flights(&context.path, false)?
+                .last()
+                .is_some_and(|last| last.created > flight.created);
+        db::append_flights(&context.path, iter::once(&flight).chain(&return_flight))?;
+        out_of_order
+    };
+
+    if !context.quiet() {
+        if out_of_order {
+            warn_out_of_order(false);
+        }
+        for flight in iter::once(&flight).chain(&return_flight) {
+            println!("{}", describe_logged(flight, context));
+        }
+    }
+    Ok(())
 }
 
-#[derive(Clone, Debug, Parser)]
-struct Args {
-    origin: String,
+/// `add --from-json`: a whole record at once, with no editor.
+fn add_json(json: &str, args: &AddArgs, context: &Context) -> io::Result<()> {
+    let json = match json {
+        "-" => io::read_to_string(io::stdin())?,
+        json => json.to_string(),
+    };
 
-    /// waypoints
-    ///
-    /// A collection of waypoints other than your point of origin. These should appear in order
-    /// and the final waypoint should be your destination.
-    #[arg(required(true))]
-    waypoints: Vec<String>,
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let flights = db::read_flights(&context.path, context.warn_unreadable())?;
+    let flight = json_flight(&json, args, &flights, context)?;
 
-    /// elapsed time
-    ///
-    /// Expressed in minutes or hours+minutes ("123" or "2+03")
-    elapsed: ElapsedTime,
+    if context.dry_run {
+        println!("{}", serde_json::to_string(&flight)?);
+        return Ok(());
+    }
+    db::append_flights(&context.path, iter::once(&flight))?;
+
+    if !context.quiet() {
+        if flights
+            .last()
+            .is_some_and(|last| last.created > flight.created)
+        {
+            warn_out_of_order(false);
+        }
+        println!("{}", describe_logged(&flight, context));
+    }
+    Ok(())
+}
 
-    /// notes on the flight
-    ///
-    /// If this field is left empty, an editor window will open and the user may save a note
-    /// there.
-    #[arg(short, long)]
-    notes: Option<String>,
+/// Warns that a newly logged flight (or, with `several`, some of a batch) is older than the last
+/// one in the log.
+fn warn_out_of_order(several: bool) {
+    let flights = if several {
+        "some of these flights are"
+    } else {
+        "this flight is"
+    };
+    eprintln!(
+        "warning: {flights} older than the last one logged; run `route sort` to put the log back \
+         in order"
+    );
 }
 
-#[serde_as]
-#[derive(Clone, Debug, Serialize)]
-struct Flight {
-    created: DateTime<Utc>,
-    waypoints: Vec<String>,
-    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
-    elapsed: Duration,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    notes: Option<String>,
+/// The confirmation printed for a flight once it's in the log.
+fn describe_logged(flight: &Flight, context: &Context) -> String {
+    let notes = if flight.notes.is_some() {
+        ", with notes"
+    } else {
+        ""
+    };
+    format!(
+        "logged {} ({}{notes})",
+        flight.waypoints.join(" -> "),
+        format_elapsed(flight.elapsed, context)
+    )
 }
 
-impl Flight {
-    fn new<T: AsRef<str>>(origin: impl AsRef<str>, waypoints: impl IntoIterator<Item = T>, elapsed: ElapsedTime) -> Self {
-        let waypoints = iter::once(origin.as_ref().to_ascii_uppercase())
-            .chain(waypoints.into_iter().map(|wpt| wpt.as_ref().to_ascii_uppercase()));
+/// Reads a flight from a JSON record and checks it as `add` checks its arguments, against the
+/// flights already logged.
+///
+/// A record must match the current schema: an unknown field is an error unless --lenient, as is
+/// any version but the current one (older records belong in `import` or `merge`).
+fn json_flight(
+    json: &str,
+    args: &AddArgs,
+    flights: &[Flight],
+    context: &Context,
+) -> io::Result<Flight> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
 
-        Self {
-            created: Utc::now(),
-            waypoints: waypoints.collect(),
-            elapsed: elapsed.into_duration(),
-            notes: None,
+    let mut record: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| invalid(format!("invalid flight record: {e}")))?;
+    let Some(fields) = record.as_object_mut() else {
+        return Err(invalid(String::from(
+            "expected a flight record as a JSON object",
+        )));
+    };
+
+    if !args.lenient {
+        let unknown: Vec<_> = fields
+            .keys()
+            .filter(|key| !flight::FIELDS.contains(&key.as_str()))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            let label = if unknown.len() == 1 {
+                "field"
+            } else {
+                "fields"
+            };
+            return Err(invalid(format!(
+                "unknown {label} {}; use --lenient to ignore",
+                unknown.join(", ")
+            )));
         }
     }
 
-    fn add_notes(&mut self, notes: impl Into<String>) {
-        self.notes = Some(notes.into())
+    match fields.get("version") {
+        None => {
+            fields.insert(String::from("version"), flight::CURRENT_VERSION.into());
+        }
+        Some(version) if *version == flight::CURRENT_VERSION => (),
+        Some(version) => {
+            return Err(invalid(format!(
+                "expected a version {} record, not version {version}",
+                flight::CURRENT_VERSION
+            )))
+        }
+    }
+    fields
+        .entry("id")
+        .or_insert_with(|| Ulid::generate().to_string().into());
+    fields
+        .entry("created")
+        .or_insert_with(|| Utc::now().to_rfc3339().into());
+
+    let mut flight: Flight = serde_json::from_value(record)
+        .map_err(|e| invalid(format!("invalid flight record: {e}")))?;
+
+    if Ulid::from_string(&flight.id).is_err() {
+        return Err(invalid(format!("id '{}' isn't a ULID", flight.id)));
+    }
+    if flights.iter().any(|logged| logged.id == flight.id) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("a flight with id {} is already logged", flight.id),
+        ));
+    }
+
+    let [origin, waypoints @ ..] = flight.waypoints.as_slice() else {
+        return Err(invalid(String::from("expected at least two waypoints")));
+    };
+    if waypoints.is_empty() {
+        return Err(invalid(String::from("expected at least two waypoints")));
     }
+    let route = flight::normalize_route(origin, waypoints)?;
+    check_repeated_waypoints(&route, args.force, context)?;
+    check_leaves_origin(&route, args.local)?;
+    if !args.no_validate {
+        flight::validate_waypoints(&route)?;
+    }
+    flight.waypoints = route;
+
+    if flight.elapsed <= Duration::zero() {
+        return Err(invalid(String::from("elapsed time must be positive")));
+    }
+    let elapsed = ElapsedTime::try_from(flight.elapsed).map_err(|e| invalid(e.to_string()))?;
+    let sub_duration = |name: &str, duration: Option<Duration>| {
+        duration
+            .map(ElapsedTime::try_from)
+            .transpose()
+            .map_err(|_| invalid(format!("{name} time is too long")))
+    };
+    flight::validate_sub_duration("night", sub_duration("night", flight.night)?, elapsed)?;
+    flight::validate_sub_duration(
+        "instrument",
+        sub_duration("instrument", flight.instrument)?,
+        elapsed,
+    )?;
+
+    if flight.created > Utc::now() {
+        return Err(invalid(String::from("flight date is in the future")));
+    }
+    if flight.landings.is_some_and(|landings| landings > 200) {
+        return Err(invalid(String::from("more than 200 landings")));
+    }
+    if flight
+        .fuel
+        .is_some_and(|fuel| !fuel.is_finite() || fuel <= 0.0)
+    {
+        return Err(invalid(String::from("expected a positive amount of fuel")));
+    }
+
+    // Tidied up the way the flags would have been.
+    if let Some(aircraft) = flight.aircraft.take() {
+        if !aircraft.trim().is_empty() {
+            flight.add_aircraft(aircraft.trim());
+        }
+    }
+    let tags = mem::take(&mut flight.tags);
+    flight.add_tags(&tags);
+    flight.notes = flight.notes.filter(|notes| !notes.is_empty());
+    check_note_size(
+        flight.notes.as_deref().unwrap_or_default(),
+        args.force,
+        context,
+    )?;
+
+    if !args.force {
+        check_duplicate(flights, &flight.waypoints, elapsed, flight.created, context)?;
+    }
+
+    Ok(flight)
 }
 
-fn main() {
-    if let Err(e) = run(&Args::parse()) {
-        eprintln!("{e}");
-        process::exit(1);
+/// Checks `add`'s arguments, short of anything that needs the log, returning the route, the
+/// elapsed time, and the date if one was given.
+fn validate_add(
+    args: &AddArgs,
+    context: &Context,
+) -> io::Result<(Vec<String>, ElapsedTime, Option<DateTime<Utc>>)> {
+    let (waypoints, elapsed) = flight::split_elapsed(&args.waypoints, args.depart, args.arrive)?;
+    let origin = args
+        .origin
+        .as_deref()
+        .expect("clap requires an origin without --from-json");
+    let route = flight::normalize_route(origin, waypoints)?;
+    check_repeated_waypoints(&route, args.force, context)?;
+    check_leaves_origin(&route, args.local)?;
+
+    if !args.no_validate {
+        flight::validate_waypoints(&route)?;
     }
+
+    flight::validate_sub_duration("night", args.night, elapsed)?;
+    flight::validate_sub_duration("instrument", args.instrument, elapsed)?;
+
+    let created = args.date.map(DateArg::start_local);
+    if created.is_some_and(|created| created > Utc::now()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "flight date is in the future",
+        ));
+    }
+
+    Ok((route, elapsed, created))
 }
 
-fn run(args: &Args) -> io::Result<()> {
-    // First off, we need to construct a writable flight model. We don't have a readable one just
-    // yet, but that's... fine. I think. Whatever.
+/// A route that never leaves its origin needs to be marked as local; otherwise it's more likely a
+/// missing destination than a trip around the pattern.
+fn check_leaves_origin(route: &[String], local: bool) -> io::Result<()> {
+    if !local && route[1..].iter().all(|waypoint| *waypoint == route[0]) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "route never leaves {}; use --local for a flight that stays at one field",
+                route[0]
+            ),
+        ));
+    }
+    Ok(())
+}
 
-    let notes = args
-        .notes
-        .as_deref()
-        .map(|message| Ok(Cow::Borrowed(message)))
-        .unwrap_or_else(|| read_from_file().map(Cow::Owned))?;
+/// Puts together the flight `add` describes, along with the flight home if there is one.
+fn build_flight(
+    args: &AddArgs,
+    context: &Context,
+    route: &[String],
+    elapsed: ElapsedTime,
+    created: Option<DateTime<Utc>>,
+    night: Option<Duration>,
+    notes: String,
+) -> (Flight, Option<Flight>) {
+    let mut flight = Flight::new(&route[0], &route[1..], elapsed);
+    flight.depart = args.depart;
+    flight.arrive = args.arrive;
+
+    if let Some(created) = created {
+        flight.created = created;
+    }
 
-    let mut flight = Flight::new(&args.origin, &args.waypoints, args.elapsed);
+    // An explicit --aircraft "" leaves the flight without one, default or no default.
+    let aircraft = match args.aircraft.as_deref() {
+        Some(aircraft) => Some(aircraft.trim()).filter(|aircraft| !aircraft.is_empty()),
+        None => context.config.default_aircraft(context.logbook.as_deref()),
+    };
+    if let Some(aircraft) = aircraft {
+        flight.add_aircraft(aircraft);
+    }
+
+    flight.landings = args.landings;
+    flight.fuel = args.fuel;
+    flight.add_tags(&args.tags);
+    flight.simulator = args.sim;
+    flight.night = night;
+    flight.instrument = args.instrument.map(ElapsedTime::into_duration);
+
+    // Built before the notes go on, which belong to the outbound leg only.
+    let return_flight = args.return_elapsed.map(|elapsed| flight.reversed(elapsed));
 
     if !notes.is_empty() {
         flight.add_notes(notes);
     }
-    
-    // Next, we need to store the flight model in a database. I use the term loosely. At present,
-    // the database will be line-delimited json.
 
-    let data = serde_json::to_string(&flight).unwrap();
-    let mut file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .append(true)
-        .open(&get_file_path()?)?;
+    (flight, return_flight)
+}
+
+/// Estimates night time for --infer-night and asks whether to log it.
+///
+/// A dry run only reports the estimate, taking it as accepted.
+fn infer_night(
+    args: &AddArgs,
+    route: &[String],
+    elapsed: ElapsedTime,
+    created: Option<DateTime<Utc>>,
+    context: &Context,
+) -> io::Result<Option<Duration>> {
+    let time = args
+        .depart
+        .expect("clap requires --depart with --infer-night");
+
+    // The answer to the prompt comes from stdin, so the note can't as well. (--from-json is
+    // turned away by clap.)
+    if !context.dry_run && args.notes.iter().any(|note| note == "-") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--infer-night asks before logging, so it can't be combined with --notes -",
+        ));
+    }
+
+    // Without --date, a departure later in the day than now must have been yesterday's.
+    let now = Utc::now();
+    let date = created.unwrap_or(now).with_timezone(&Local).date_naive();
+    let mut depart = local_instant(date.and_time(time));
+    if created.is_none() && depart > now {
+        depart = local_instant((date - Duration::days(1)).and_time(time));
+    }
+
+    let Some(night) = night::estimate(route, depart, elapsed.into_duration()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "can't infer night time without knowing where {} is",
+                airport::unknown_waypoints(route).join(", ")
+            ),
+        ));
+    };
+
+    if night.is_zero() {
+        if !context.quiet() {
+            eprintln!("no night time estimated");
+        }
+        return Ok(None);
+    }
+
+    let estimate = format_elapsed(night, context);
+    if context.dry_run {
+        eprintln!("estimated night time: {estimate}");
+        return Ok(Some(night));
+    }
+    Ok(confirm(&format!("estimated night time: {estimate}; log it?"))?.then_some(night))
+}
+
+/// Reads a local date and time, falling back to UTC for one that DST skips over.
+fn local_instant(local: NaiveDateTime) -> DateTime<Utc> {
+    Local
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|local| local.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+/// Refuses a flight that looks like one already logged (see `flight::find_duplicate`).
+fn check_duplicate(
+    flights: &[Flight],
+    route: &[String],
+    elapsed: ElapsedTime,
+    created: DateTime<Utc>,
+    context: &Context,
+) -> io::Result<()> {
+    match flight::find_duplicate(flights, route, elapsed, created) {
+        Some(flight) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "this looks like a duplicate of the flight logged {}; use --force to log it anyway",
+                format_created(flight.created, context.utc)
+            ),
+        )),
+        None => Ok(()),
+    }
+}
 
-    Ok(writeln!(file, "{data}")?)
+/// Every flight is one line in the log, and every read pays for the longest of them, so a huge
+/// note needs `--force`. With it, there's still a warning.
+fn check_note_size(notes: &str, force: bool, context: &Context) -> io::Result<()> {
+    if notes.len() <= notes::MAX_LEN {
+        return Ok(());
+    }
+
+    let size = notes.len().div_ceil(1024);
+    if !force {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "notes are {size} KiB, over the {} KiB limit; use --force to log them anyway",
+                notes::MAX_LEN / 1024
+            ),
+        ));
+    }
+
+    if !context.quiet() {
+        eprintln!("warning: notes are {size} KiB");
+    }
+    Ok(())
+}
+
+/// Refuses a waypoint repeated back to back, or with `--force` just warns about it.
+fn check_repeated_waypoints(route: &[String], force: bool, context: &Context) -> io::Result<()> {
+    let Some(waypoint) = flight::repeated_waypoint(route) else {
+        return Ok(());
+    };
+
+    if force {
+        if !context.quiet() {
+            eprintln!("warning: {waypoint} appears twice in a row");
+        }
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{waypoint} appears twice in a row; use --force to log it anyway"),
+        ))
+    }
 }
 
-fn read_from_file() -> io::Result<String> {
-    static HELP_MESSAGE: &str = include_str!("../resource/help_message.txt");
+fn list(args: &ListArgs, context: &Context) -> io::Result<()> {
+    let table = matches!(args.format, ListFormat::Text) && !args.map && io::stdout().is_terminal();
+
+    // The logbook is read a flight at a time, and only what's going to be shown is kept: the
+    // last N matches for --tail, or just the one page for --page. Plain text and markdown with
+    // neither keep nothing at all, printing each flight as it's read (JSON a record at a time
+    // between the brackets); only a table has to be seen whole, to size its columns.
+    let stream = args.limit.is_none() && args.page.is_none() && !table;
+    let page_size = args.page_size as usize;
+    let window = match (args.limit, args.page) {
+        (None, Some(page)) => Some((page as usize - 1) * page_size..page as usize * page_size),
+        _ => None,
+    };
+
+    // Locked for the duration, so a long listing isn't taken apart and put back together a line
+    // at a time -- and any error, such as a pager that's quit, comes back to be handled.
+    let mut out = io::stdout().lock();
+    if stream {
+        write_list_open(&mut out, args)?;
+    }
+
+    let _lock = db::lock(&context.path, LockMode::Shared)?;
+    let mut flights = VecDeque::new();
+    let mut matched = 0;
+    let mut unknown = 0;
+    let mut printed = Ok(());
+    db::for_each_flight(&context.path, context.warn_unreadable(), |flight| {
+        if !is_listed(args, &flight) {
+            return;
+        }
+
+        // Distance depends on the units in play, so it's filtered here rather than in is_listed.
+        if args.min_distance.is_some() || args.max_distance.is_some() {
+            let Some(distance) = airport::route_distance(&flight.waypoints) else {
+                unknown += 1;
+                return;
+            };
+            let distance = airport::convert_nm(distance, context.units);
+            if !(args.min_distance.is_none_or(|min| distance >= min)
+                && args.max_distance.is_none_or(|max| distance <= max))
+            {
+                return;
+            }
+        }
+
+        matched += 1;
+        if stream {
+            if printed.is_ok() {
+                printed = write_listed(&mut out, &flight, matched - 1, args, context);
+            }
+        } else if let Some(window) = &window {
+            if window.contains(&(matched - 1)) {
+                flights.push_back(flight);
+            }
+        } else {
+            // The log is in the order flights were added, so the most recent are at the end.
+            flights.push_back(flight);
+            if args.limit.is_some_and(|limit| flights.len() > limit) {
+                flights.pop_front();
+            }
+        }
+    })?;
+    printed?;
+
+    if unknown > 0 && !context.quiet() {
+        let flights = if unknown == 1 { "flight" } else { "flights" };
+        eprintln!("left out {unknown} {flights} of unknown distance");
+    }
 
-    let path = env::temp_dir().join("EDIT_NOTE");
+    let mut footer = None;
+    if let Some(page) = args.page {
+        let page = page as usize;
+        let total = if args.limit.is_some() {
+            flights.len()
+        } else {
+            matched
+        };
+        let pages = total.div_ceil(page_size).max(1);
+        if page > pages {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("there's no page {page}; the last is {pages}"),
+            ));
+        }
+        if args.limit.is_some() {
+            let start = (page - 1) * page_size;
+            flights = flights.into_iter().skip(start).take(page_size).collect();
+        }
+        footer = Some(format!("page {page}/{pages}"));
+    }
 
-    fs::write(&path, HELP_MESSAGE)?;
-    Command::new(EDITOR).arg(&path).status()?;
+    if table {
+        // At a terminal, flights get a table; anywhere else, the plain blocks are easier to pick
+        // apart.
+        let rows: Vec<_> = flights
+            .iter()
+            .map(|flight| table::Row {
+                cells: [
+                    format_date(flight.created, context.utc),
+                    flight.waypoints.join(" -> "),
+                    format_elapsed(flight.elapsed, context),
+                    airport::route_distance(&flight.waypoints)
+                        .map(|distance| {
+                            let distance = airport::convert_nm(distance, context.units);
+                            format!("{distance:.1} {}", context.units)
+                        })
+                        .unwrap_or_else(|| String::from("?")),
+                    flight.aircraft.clone().unwrap_or_default(),
+                ],
+                id: flight.id.clone(),
+                notes: flight.notes.clone(),
+            })
+            .collect();
+        table::print(&rows, table::use_color(context.no_color), table::width());
+    } else if !stream {
+        write_list_open(&mut out, args)?;
+        for (position, flight) in flights.iter().enumerate() {
+            write_listed(&mut out, flight, position, args, context)?;
+        }
+        write_list_close(&mut out, args, flights.len())?;
+    } else {
+        write_list_close(&mut out, args, matched)?;
+    }
+
+    // On stderr, so a page of JSON is still just JSON.
+    if let Some(footer) = footer.filter(|_| !context.quiet()) {
+        eprintln!("{footer}");
+    }
 
-    fs::read_to_string(&path).map(strip_comments)
+    Ok(())
+}
+
+/// Writes what comes before the first flight: a markdown table's header, or a JSON array's
+/// opening bracket.
+fn write_list_open(out: &mut impl Write, args: &ListArgs) -> io::Result<()> {
+    match args.format {
+        ListFormat::Text => {}
+        ListFormat::Markdown => {
+            writeln!(out, "| Date | Route | Time | Aircraft | Notes |")?;
+            writeln!(out, "| --- | --- | --- | --- | --- |")?;
+        }
+        ListFormat::Json => write!(out, "[")?,
+    }
+    Ok(())
 }
 
-fn strip_comments(notes: String) -> String {
-    let mut buf = String::with_capacity(notes.len());
+/// Closes a JSON array of `count` flights, laid out as `serde_json::to_string_pretty` would.
+fn write_list_close(out: &mut impl Write, args: &ListArgs, count: usize) -> io::Result<()> {
+    if matches!(args.format, ListFormat::Json) {
+        writeln!(out, "{}]", if count == 0 { "" } else { "\n" })?;
+    }
+    Ok(())
+}
 
-    for line in notes.lines() {
-        if !line.starts_with('#') {
-            buf.push_str(line);
-            buf.push('\n');
+/// Writes one flight as plain text, a markdown row or a JSON array element, whichever `args`
+/// asks for. `position` counts the flights printed before this one.
+fn write_listed(
+    out: &mut impl Write,
+    flight: &Flight,
+    position: usize,
+    args: &ListArgs,
+    context: &Context,
+) -> io::Result<()> {
+    match args.format {
+        ListFormat::Text => {
+            write_flight(out, flight, context)?;
+            if args.map {
+                match sketch::render(&flight.waypoints) {
+                    Some(sketch) => writeln!(out, "{sketch}")?,
+                    None => writeln!(out, "(no map) {}", flight.waypoints.join(" -> "))?,
+                }
+            }
+            writeln!(out)?;
+        }
+        ListFormat::Markdown => writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            format_date(flight.created, context.utc),
+            markdown_escape(&flight.waypoints.join(" -> ")),
+            format_elapsed(flight.elapsed, context),
+            markdown_escape(flight.aircraft.as_deref().unwrap_or_default()),
+            markdown_escape(flight.notes.as_deref().unwrap_or_default()),
+        )?,
+        ListFormat::Json => {
+            let separator = if position == 0 { "" } else { "," };
+            let record = serde_json::to_string_pretty(flight)?;
+            write!(out, "{separator}\n  {}", record.replace('\n', "\n  "))?;
         }
     }
+    Ok(())
+}
 
-    // If it sucks but it works, it... still sucks.
-    if buf.ends_with('\n') {
-        buf.truncate(buf.len() - 1);
+/// Makes text safe for a markdown table cell, where a pipe ends the cell and a newline ends the
+/// table.
+fn markdown_escape(text: &str) -> String {
+    text.replace('|', "\\|")
+        .lines()
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+fn is_listed(args: &ListArgs, flight: &Flight) -> bool {
+    let matches = |code: &String, waypoint: Option<&String>| {
+        waypoint.is_some_and(|waypoint| waypoint.eq_ignore_ascii_case(code))
+    };
+
+    args.since
+        .is_none_or(|since| flight.created >= since.start())
+        && args.until.is_none_or(|until| flight.created <= until.end())
+        && args.tag.as_ref().is_none_or(|tag| flight.has_tag(tag))
+        && (!args.sim_only || flight.simulator)
+        && (!args.no_sim || !flight.simulator)
+        && (!args.cross_country_only || flight.is_cross_country() == Some(true))
+        && args.via.as_ref().is_none_or(|code| {
+            flight
+                .waypoints
+                .iter()
+                .any(|waypoint| matches(code, Some(waypoint)))
+        })
+        && args
+            .from
+            .as_ref()
+            .is_none_or(|code| matches(code, flight.waypoints.first()))
+        && args
+            .to
+            .as_ref()
+            .is_none_or(|code| matches(code, flight.waypoints.last()))
+}
+
+fn write_flight(out: &mut impl Write, flight: &Flight, context: &Context) -> io::Result<()> {
+    writeln!(
+        out,
+        "{}  {}",
+        format_created(flight.created, context.utc),
+        flight.id
+    )?;
+    writeln!(out, "{}", flight.waypoints.join(" -> "))?;
+    match (flight.depart, flight.arrive) {
+        (Some(depart), Some(arrive)) => writeln!(
+            out,
+            "{} ({}-{})",
+            format_elapsed(flight.elapsed, context),
+            depart.format("%R"),
+            arrive.format("%R")
+        )?,
+        _ => writeln!(out, "{}", format_elapsed(flight.elapsed, context))?,
+    }
+    if let Some(night) = flight.night {
+        writeln!(out, "night {}", format_elapsed(night, context))?;
+    }
+    if let Some(instrument) = flight.instrument {
+        writeln!(out, "instrument {}", format_elapsed(instrument, context))?;
     }
+    match flight.is_cross_country() {
+        Some(true) => writeln!(
+            out,
+            "{} (cross-country)",
+            format_distance(&flight.waypoints, context.units)
+        )?,
+        _ => writeln!(out, "{}", format_distance(&flight.waypoints, context.units))?,
+    }
+    if let Some(groundspeed) = flight.groundspeed() {
+        writeln!(
+            out,
+            "groundspeed {}",
+            format_speed(groundspeed, context.units)
+        )?;
+    }
+    if let Some(fuel) = flight.fuel {
+        writeln!(
+            out,
+            "fuel {}",
+            describe_fuel(fuel, flight.burn_rate(), flight.fuel_economy(), context)
+        )?;
+    }
+    if let Some(aircraft) = &flight.aircraft {
+        writeln!(out, "{aircraft}")?;
+    }
+    if flight.simulator {
+        writeln!(out, "simulator")?;
+    }
+    if let Some(landings) = flight.landings {
+        writeln!(out, "{landings} landings")?;
+    }
+    if !flight.tags.is_empty() {
+        writeln!(out, "tags: {}", flight.tags.join(", "))?;
+    }
+    if let Some(notes) = &flight.notes {
+        writeln!(out, "{notes}")?;
+    }
+    Ok(())
+}
+
+/// Timestamps are stored in UTC, but most people would rather read them in their own timezone.
+fn format_created(created: DateTime<Utc>, utc: bool) -> String {
+    if utc {
+        created.format("%F %R UTC").to_string()
+    } else {
+        created
+            .with_timezone(&Local)
+            .format("%F %R %:z")
+            .to_string()
+    }
+}
+
+fn delete(args: &DeleteArgs, context: &Context) -> io::Result<()> {
+    // Held across the confirmation prompt, so nothing new can land between reading the log and
+    // writing it back.
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let (mut flights, unreadable) = db::read_entries(&context.path)?;
+    let flight = match &args.id {
+        Some(id) => flights.remove(flight::find_by_id(&flights, id)?),
+        None => flights
+            .pop()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no flights to delete"))?,
+    };
 
-    buf
+    // Without --yes, the flight is shown regardless; nobody should confirm a delete blind.
+    if !context.quiet() || !args.yes {
+        write_flight(&mut io::stdout(), &flight, context)?;
+    }
+
+    if context.dry_run || (!args.yes && !confirm("delete this flight?")?) {
+        return Ok(());
+    }
+
+    // Lines we couldn't read go back as they were; deleting one flight shouldn't cost any others.
+    db::write_entries(&context.path, &flights, &unreadable)
 }
 
-fn get_file_path() -> io::Result<PathBuf> {
-    let dirs = ProjectDirs::from("", "Hack Commons", "route").unwrap();
-    let dir = dirs.data_dir();
+fn last(context: &Context) -> io::Result<()> {
+    let flight = {
+        let _lock = db::lock(&context.path, LockMode::Shared)?;
+        db::read_last(&context.path, context.warn_unreadable())?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no flights logged"))?
+    };
+    write_flight(&mut io::stdout(), &flight, context)
+}
+
+fn edit(args: &EditArgs, context: &Context) -> io::Result<()> {
+    let original = {
+        let _lock = db::lock(&context.path, LockMode::Shared)?;
+        let flights = db::read_flights(&context.path, context.warn_unreadable())?;
+        match (&args.id, args.index) {
+            (Some(id), _) => flights[flight::find_by_id(&flights, id)?].clone(),
+            (None, index) => select_flight(&flights, index.unwrap_or_default())?.clone(),
+        }
+    };
 
-    if !dir.exists() {
-        fs::create_dir_all(dir)?;
+    let notes = notes::read_from_file(
+        context.config.editor.as_deref(),
+        original.notes.as_deref().unwrap_or_default(),
+    )?;
+
+    // The note's already written by now, so throwing it out would only make things worse.
+    check_note_size(&notes, true, context)?;
+
+    // The editor may have been open a while, so take a fresh look at the log before writing it
+    // back and make sure the flight is still there.
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let (mut flights, unreadable) = db::read_entries(&context.path)?;
+    let Some(flight) = flights.iter_mut().find(|flight| flight.id == original.id) else {
+        return Err(io::Error::other(
+            "the log changed while the editor was open; notes not saved",
+        ));
+    };
+
+    flight.notes = (!notes.is_empty()).then_some(notes);
+
+    if context.dry_run {
+        println!("{}", serde_json::to_string(flight)?);
+        return Ok(());
     }
 
-    Ok(dir.join("db.json"))
+    db::write_entries(&context.path, &flights, &unreadable)
+}
+
+fn note(context: &Context) -> io::Result<()> {
+    let original = {
+        let _lock = db::lock(&context.path, LockMode::Shared)?;
+        db::read_last(&context.path, context.warn_unreadable())?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no flights to add notes to"))?
+    };
+
+    let addition = notes::read_addition(
+        context.config.editor.as_deref(),
+        original.notes.as_deref().unwrap_or_default(),
+    )?;
+    if addition.is_empty() {
+        if !context.quiet() {
+            println!("nothing to add");
+        }
+        return Ok(());
+    }
+
+    // As with edit, the log may have moved on while the editor was open. The id tells the legs of
+    // a round trip apart, where the time they were logged might not.
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let (mut flights, unreadable) = db::read_entries(&context.path)?;
+    let flight = match flights.last_mut() {
+        Some(flight) if flight.id == original.id => flight,
+        _ => {
+            return Err(io::Error::other(
+                "the log changed while the editor was open; notes not saved",
+            ))
+        }
+    };
+
+    let notes = match flight.notes.take() {
+        Some(notes) => format!("{notes}\n\n{addition}"),
+        None => addition,
+    };
+    check_note_size(&notes, true, context)?;
+    flight.add_notes(notes);
+
+    if context.dry_run {
+        println!("{}", serde_json::to_string(flight)?);
+        return Ok(());
+    }
+
+    db::write_entries(&context.path, &flights, &unreadable)
+}
+
+fn export(args: &ExportArgs, context: &Context) -> io::Result<()> {
+    let _lock = db::lock(&context.path, LockMode::Shared)?;
+    let flights = db::read_flights(&context.path, context.warn_unreadable())?;
+
+    let out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).with_path("create", path)?,
+        )),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    match args.format {
+        ExportFormat::Csv => match args.index {
+            Some(index) => export::write_csv(out, [select_flight(&flights, index)?]),
+            None => export::write_csv(out, &flights),
+        },
+        ExportFormat::Gpx => {
+            // Clap requires an index for GPX.
+            let index = args.index.unwrap();
+            export::write_gpx(out, select_flight(&flights, index)?)
+        }
+        ExportFormat::Ics => match args.index {
+            Some(index) => export::write_ics(out, [select_flight(&flights, index)?], Utc::now()),
+            None => export::write_ics(out, &flights, Utc::now()),
+        },
+        ExportFormat::Kml => {
+            let skipped = match args.index {
+                Some(index) => export::write_kml(out, [select_flight(&flights, index)?])?,
+                None => export::write_kml(out, &flights)?,
+            };
+            if skipped > 0 && !context.quiet() {
+                let flights = if skipped == 1 { "flight" } else { "flights" };
+                eprintln!("skipped {skipped} {flights} with unknown waypoints");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn import(args: &ImportArgs, context: &Context) -> io::Result<()> {
+    let text = fs::read_to_string(&args.file).with_path("read", &args.file)?;
+    let imported = match args.format {
+        ImportFormat::Csv => import::read_csv(&text)?,
+    };
+
+    if !imported.skipped.is_empty() && context.warn_unreadable() {
+        let lines: Vec<_> = imported
+            .skipped
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        eprintln!("skipped unusable rows (lines {})", lines.join(", "));
+    }
+
+    // As in batch, each row is checked against the log as it stood plus the rows before it.
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let mut flights = db::read_flights(&context.path, context.warn_unreadable())?;
+    let logged = flights.len();
+    let latest = flights.iter().map(|flight| flight.created).max();
+    let mut seen: HashSet<_> = flights.iter().map(merge_key).collect();
+
+    let mut duplicates = 0;
+    let mut failed = 0;
+    for (line, flight) in iter::zip(imported.lines, imported.flights) {
+        match import_flight(flight, &flights, &seen, args, context) {
+            Ok(Some(flight)) => {
+                seen.insert(merge_key(&flight));
+                flights.push(flight);
+            }
+            Ok(None) => duplicates += 1,
+            Err(e) => {
+                failed += 1;
+                eprintln!("line {line}: {e}");
+            }
+        }
+    }
+
+    let new = &flights[logged..];
+    if !context.dry_run && !new.is_empty() {
+        db::append_flights(&context.path, new)?;
+    }
+
+    if !context.quiet() {
+        if latest.is_some_and(|latest| new.iter().any(|flight| flight.created < latest)) {
+            warn_out_of_order(true);
+        }
+
+        let count = new.len();
+        let verb = if context.dry_run {
+            "would import"
+        } else {
+            "imported"
+        };
+        let noun = if count == 1 { "flight" } else { "flights" };
+        let unusable = imported.skipped.len() + failed;
+        println!(
+            "{verb} {count} {noun}, skipped {duplicates} already logged and {unusable} unusable"
+        );
+    }
+    Ok(())
+}
+
+/// Checks an imported flight as `add` checks its arguments, or passes it over (`None`) if it's
+/// already logged.
+fn import_flight(
+    mut flight: Flight,
+    flights: &[Flight],
+    seen: &HashSet<MergeKey>,
+    args: &ImportArgs,
+    context: &Context,
+) -> io::Result<Option<Flight>> {
+    let (origin, waypoints) = flight
+        .waypoints
+        .split_first()
+        .expect("an imported route has at least two waypoints");
+    flight.waypoints = flight::normalize_route(origin, waypoints)?;
+    if seen.contains(&merge_key(&flight)) {
+        return Ok(None);
+    }
+
+    // A row has no way to say --local, so a route that never leaves its origin is taken as meant.
+    check_repeated_waypoints(&flight.waypoints, args.force, context)?;
+    if !args.no_validate {
+        flight::validate_waypoints(&flight.waypoints)?;
+    }
+    if flight.created > Utc::now() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "flight date is in the future",
+        ));
+    }
+    check_note_size(
+        flight.notes.as_deref().unwrap_or_default(),
+        args.force,
+        context,
+    )?;
+    if !args.force {
+        let elapsed = ElapsedTime::try_from(flight.elapsed)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        check_duplicate(flights, &flight.waypoints, elapsed, flight.created, context)?;
+    }
+
+    Ok(Some(flight))
+}
+
+fn batch(args: &BatchArgs, context: &Context) -> io::Result<()> {
+    let text = fs::read_to_string(&args.file).with_path("read", &args.file)?;
+
+    // The whole batch is checked and written under one lock, each line against the log as it
+    // stood plus the lines before it.
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let mut flights = db::read_flights(&context.path, context.warn_unreadable())?;
+    let logged = flights.len();
+    let latest = flights.iter().map(|flight| flight.created).max();
+
+    // The line each new flight came from, for the report.
+    let mut sources = Vec::new();
+    let mut failed = 0;
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match batch_flight(line, &flights, context) {
+            Ok((flight, return_flight)) => {
+                for flight in iter::once(flight).chain(return_flight) {
+                    sources.push(idx + 1);
+                    flights.push(flight);
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("line {}: {e}", idx + 1);
+            }
+        }
+    }
+
+    let new = &flights[logged..];
+    if context.dry_run {
+        for flight in new {
+            println!("{}", serde_json::to_string(flight)?);
+        }
+    } else if !new.is_empty() {
+        db::append_flights(&context.path, new)?;
+    }
+
+    let count = new.len();
+    let verb = if context.dry_run {
+        "would log"
+    } else {
+        "logged"
+    };
+    let noun = if count == 1 { "flight" } else { "flights" };
+    let lines = if failed == 1 { "line" } else { "lines" };
+    let summary = format!("{verb} {count} {noun}, {failed} {lines} failed");
+
+    if !context.quiet() {
+        if !context.dry_run {
+            for (line, flight) in iter::zip(&sources, new) {
+                println!("line {line}: {}", describe_logged(flight, context));
+            }
+        }
+
+        if latest.is_some_and(|latest| new.iter().any(|flight| flight.created < latest)) {
+            warn_out_of_order(true);
+        }
+    }
+
+    // The good lines are logged either way, but a script running the batch should hear about the
+    // bad ones.
+    if failed > 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, summary));
+    }
+    if !context.quiet() {
+        println!("{summary}");
+    }
+    Ok(())
+}
+
+/// Reads one line of a batch into a flight, and the flight home if it has one.
+fn batch_flight(
+    line: &str,
+    flights: &[Flight],
+    context: &Context,
+) -> io::Result<(Flight, Option<Flight>)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    let words = batch::split_words(line).map_err(invalid)?;
+    let args = match BatchLine::try_parse_from(words) {
+        Ok(line) => line.add,
+        Err(e) => {
+            // Just the complaint, without the usage clap would print after it.
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            return Err(invalid(
+                message
+                    .strip_prefix("error: ")
+                    .unwrap_or(message)
+                    .to_string(),
+            ));
+        }
+    };
+    if let Some(json) = &args.from_json {
+        if json == "-" {
+            return Err(invalid(String::from(
+                "--from-json - reads from stdin, so it can't be used in a batch",
+            )));
+        }
+        return Ok((json_flight(json, &args, flights, context)?, None));
+    }
+    if args.infer_night {
+        return Err(invalid(String::from(
+            "--infer-night asks before logging, so it can't be used in a batch",
+        )));
+    }
+
+    let (route, elapsed, created) = validate_add(&args, context)?;
+    if !args.force {
+        let created = created.unwrap_or_else(Utc::now);
+        check_duplicate(flights, &route, elapsed, created, context)?;
+    }
+
+    let notes = match args.notes.as_slice() {
+        [] => match &args.notes_file {
+            Some(path) => notes::read_from_path(path)?,
+            None => String::new(),
+        },
+        notes if notes.iter().any(|note| note == "-") => {
+            return Err(invalid(String::from(
+                "--notes - reads from stdin, so it can't be used in a batch",
+            )))
+        }
+        notes => notes::from_args(notes),
+    };
+    check_note_size(&notes, args.force, context)?;
+
+    let night = args.night.map(ElapsedTime::into_duration);
+    Ok(build_flight(
+        &args, context, &route, elapsed, created, night, notes,
+    ))
+}
+
+/// What makes two flights the same to merge and import: the same route for the same time,
+/// logged in the same second. Seconds are as fine as an export goes.
+type MergeKey = (DateTime<Utc>, Vec<String>, Duration);
+
+fn merge_key(flight: &Flight) -> MergeKey {
+    (
+        flight.created.trunc_subsecs(0),
+        flight.waypoints.clone(),
+        flight.elapsed,
+    )
+}
+
+fn merge(args: &MergeArgs, context: &Context) -> io::Result<()> {
+    if !args.other.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no logbook at {}", args.other.display()),
+        ));
+    }
+    if context.path.canonicalize().ok() == args.other.canonicalize().ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "can't merge a logbook into itself",
+        ));
+    }
+
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let (mut flights, unreadable) = db::read_entries(&context.path)?;
+    let other = {
+        let _lock = db::lock(&args.other, LockMode::Shared)?;
+        db::read_flights(&args.other, context.warn_unreadable())?
+    };
+
+    let mut seen: HashSet<_> = flights.iter().map(merge_key).collect();
+
+    let mut added = 0;
+    let mut duplicates = 0;
+    for flight in other {
+        if seen.insert(merge_key(&flight)) {
+            flights.push(flight);
+            added += 1;
+        } else {
+            duplicates += 1;
+        }
+    }
+
+    if !context.quiet() {
+        println!("added {added} flights, skipped {duplicates} duplicates");
+    }
+    if context.dry_run || added == 0 {
+        return Ok(());
+    }
+
+    flights.sort_by_key(|flight| flight.created);
+    db::write_entries(&context.path, &flights, &unreadable)
+}
+
+fn sort(args: &SortArgs, context: &Context) -> io::Result<()> {
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let (mut flights, unreadable) = db::read_entries(&context.path)?;
+
+    let in_order = flights.is_sorted_by_key(|flight| flight.created);
+    if in_order && unreadable.is_empty() {
+        if !context.quiet() {
+            println!("already in order");
+        }
+        return Ok(());
+    }
+
+    flights.sort_by_key(|flight| flight.created);
+    if !context.quiet() {
+        println!("sorted {} flights", flights.len());
+    }
+
+    let kept = if args.keep_unreadable {
+        unreadable
+    } else {
+        match unreadable.len() {
+            _ if context.quiet() => (),
+            0 => (),
+            1 => println!("dropped 1 unreadable entry"),
+            count => println!("dropped {count} unreadable entries"),
+        }
+        Vec::new()
+    };
+
+    if context.dry_run {
+        return Ok(());
+    }
+
+    db::write_entries(&context.path, &flights, &kept)
+}
+
+fn backup(args: &BackupArgs, context: &Context) -> io::Result<()> {
+    if !context.path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no logbook to back up",
+        ));
+    }
+
+    let destination = match &args.output {
+        Some(path) => path.clone(),
+        None => {
+            // db.json becomes db-TIMESTAMP.json; db-NAME.json becomes db-NAME-TIMESTAMP.json.
+            // A SQLite logbook keeps its own extension.
+            let stem = context
+                .path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let extension = if db::is_sqlite(&context.path) {
+                "sqlite"
+            } else {
+                "json"
+            };
+            let name = format!("{stem}-{}.{extension}", Local::now().format("%Y%m%d%H%M%S"));
+            context.path.with_file_name(name)
+        }
+    };
+
+    if context.dry_run {
+        println!("would back up to {}", destination.display());
+        return Ok(());
+    }
+
+    let _lock = db::lock(&context.path, LockMode::Shared)?;
+    let count = db::backup(&context.path, &destination)?;
+    if !context.quiet() {
+        let flights = if count == 1 { "flight" } else { "flights" };
+        println!("backed up {count} {flights} to {}", destination.display());
+    }
+    Ok(())
+}
+
+fn archive(args: &ArchiveArgs, context: &Context) -> io::Result<()> {
+    let before = if context.utc {
+        args.before.start()
+    } else {
+        args.before.start_local()
+    };
+
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let (flights, unreadable) = db::read_entries(&context.path)?;
+    let (archived, kept): (Vec<_>, Vec<_>) = flights
+        .into_iter()
+        .partition(|flight| flight.created < before);
+    if archived.is_empty() {
+        if !context.quiet() {
+            println!("nothing to archive");
+        }
+        return Ok(());
+    }
+
+    let mut by_year: BTreeMap<i32, Vec<&Flight>> = BTreeMap::new();
+    for flight in &archived {
+        let year = if context.utc {
+            flight.created.year()
+        } else {
+            flight.created.with_timezone(&Local).year()
+        };
+        by_year.entry(year).or_default().push(flight);
+    }
+
+    // db-NAME.json archives to archive-NAME-YYYY.json, and db.json to archive-YYYY.json.
+    let stem = context
+        .path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let prefix = match stem.strip_prefix("db") {
+        Some(name) => format!("archive{name}"),
+        None => format!("archive-{stem}"),
+    };
+
+    if !context.dry_run {
+        // The archives are written first: if we die before the logbook is rewritten, the worst
+        // case is a flight in both places rather than in neither.
+        for (year, flights) in &by_year {
+            let path = context.path.with_file_name(format!("{prefix}-{year}.json"));
+            db::append_flights(&path, flights.iter().copied())?;
+        }
+        db::write_entries(&context.path, &kept, &unreadable)?;
+    }
+
+    if !context.quiet() {
+        let flights = if archived.len() == 1 {
+            "flight"
+        } else {
+            "flights"
+        };
+        let verb = if context.dry_run {
+            "would archive"
+        } else {
+            "archived"
+        };
+        println!(
+            "{verb} {} {flights}, {} remaining",
+            archived.len(),
+            kept.len()
+        );
+        for (year, flights) in &by_year {
+            println!("  {prefix}-{year}.json  {}", flights.len());
+        }
+    }
+    Ok(())
+}
+
+fn verify(context: &Context) -> io::Result<()> {
+    if !context.path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no logbook to verify",
+        ));
+    }
+
+    let _lock = db::lock(&context.path, LockMode::Shared)?;
+    let (valid, corrupt) = db::verify(&context.path)?;
+    if corrupt.is_empty() {
+        println!("{valid} valid");
+        return Ok(());
+    }
+
+    let unit = if db::is_sqlite(&context.path) {
+        "row"
+    } else {
+        "line"
+    };
+    for entry in &corrupt {
+        println!("{unit} {}: {}", entry.line, entry.reason);
+    }
+    let lines: Vec<_> = corrupt.iter().map(|entry| entry.line.to_string()).collect();
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{valid} valid, {} corrupt at {unit}s {}",
+            corrupt.len(),
+            lines.join(", ")
+        ),
+    ))
+}
+
+#[cfg(feature = "sqlite")]
+fn migrate_to_sqlite(context: &Context) -> io::Result<()> {
+    if db::is_sqlite(&context.path) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is already a SQLite logbook", context.path.display()),
+        ));
+    }
+    if !context.path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no logbook to migrate",
+        ));
+    }
+
+    let destination = context.path.with_extension("sqlite");
+    let _lock = db::lock(&context.path, LockMode::Exclusive)?;
+    let flights = db::read_flights(&context.path, context.warn_unreadable())?;
+    let count = flights.len();
+    let noun = if count == 1 { "flight" } else { "flights" };
+    if context.dry_run {
+        println!("would migrate {count} {noun} to {}", destination.display());
+        return Ok(());
+    }
+
+    db::write_flights(&destination, &flights)?;
+    if !context.quiet() {
+        println!("migrated {count} {noun} to {}", destination.display());
+    }
+    Ok(())
+}
+
+fn logbooks(context: &Context) -> io::Result<()> {
+    let dir = context.path.parent().unwrap_or(Path::new("."));
+    // Compared by stem, so the marker follows a logbook that has been migrated to SQLite.
+    let current = context.path.file_stem();
+
+    for logbook in db::logbooks(dir)? {
+        let stem = match &logbook {
+            Some(name) => format!("db-{name}"),
+            None => String::from("db"),
+        };
+        let marker = if current == Some(stem.as_ref()) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{marker} {}", logbook.as_deref().unwrap_or("(default)"));
+    }
+
+    Ok(())
+}
+
+/// Picks a flight by its position in the log, counting from 1 at the oldest entry.
+fn select_flight(flights: &[Flight], index: usize) -> io::Result<&Flight> {
+    index
+        .checked_sub(1)
+        .and_then(|idx| flights.get(idx))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no flight at index {index} ({} flights logged)",
+                    flights.len()
+                ),
+            )
+        })
+}
+
+fn confirm(prompt: &str) -> io::Result<bool> {
+    print!("{prompt} [y/N] ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn stats(args: &StatsArgs, context: &Context) -> io::Result<()> {
+    let _lock = db::lock(&context.path, LockMode::Shared)?;
+    let since = args.since.map(|window| window.start(Utc::now()));
+
+    if let Some(by) = args.by {
+        let mut groups = stats::Groups::new(by, context.utc, args.bidirectional);
+        read_since(context, since, |flight| groups.add(flight))?;
+        let groups = groups.finish();
+        let width = groups.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        for (key, totals) in groups {
+            println!(
+                "{key:<width$}  {:>4} flights  {:>4} landings  {:>8}",
+                totals.count,
+                totals.landings,
+                format_elapsed(totals.elapsed, context)
+            );
+        }
+        return Ok(());
+    }
+
+    if args.sparkline {
+        let months = stats::recent_months(Utc::now(), args.months, context.utc);
+        let mut groups = stats::Groups::new(GroupBy::Month, context.utc, false);
+        read_since(context, since, |flight| groups.add(flight))?;
+        let groups: HashMap<_, _> = groups.finish().into_iter().collect();
+        let hours: Vec<_> = months
+            .iter()
+            .map(|month| {
+                groups
+                    .get(month)
+                    .map_or(Duration::zero(), |totals| totals.elapsed)
+            })
+            .collect();
+
+        let (first, last) = (&months[0], &months[months.len() - 1]);
+        println!("{first} {} {last}", stats::sparkline(&hours));
+        if let Some((month, peak)) = iter::zip(&months, &hours).max_by_key(|(_, hours)| **hours) {
+            if !peak.is_zero() {
+                print_stat(
+                    "busiest",
+                    format_args!("{} ({month})", format_elapsed(*peak, context)),
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    if args.airports {
+        let mut visits = stats::Visits::default();
+        read_since(context, since, |flight| visits.add(flight))?;
+        let visits = visits.finish();
+        for (airport, count) in &visits {
+            println!("{airport:<8}{count:>5}");
+        }
+        println!();
+        print_stat("unique airports", visits.len());
+        return Ok(());
+    }
+
+    let mut summary = stats::Summary::default();
+    read_since(context, since, |flight| summary.add(flight))?;
+    let totals = summary.totals;
+    if let Some(since) = since {
+        print_stat("since", format_date(since, context.utc));
+    }
+    print_stat("flights", totals.count);
+    print_stat("total", format_elapsed(totals.elapsed, context));
+
+    // Sim time doesn't count for most purposes, so it gets broken out -- but only for those who
+    // log any.
+    let sim = summary.simulator;
+    if sim.count > 0 {
+        print_stat(
+            "aircraft",
+            format_elapsed(totals.elapsed - sim.elapsed, context),
+        );
+        print_stat("simulator", format_elapsed(sim.elapsed, context));
+    }
+
+    print_stat("landings", totals.landings);
+
+    if let (Some(longest), Some(shortest)) = (&summary.longest, &summary.shortest) {
+        print_stat("longest", describe_flight(longest, context));
+        print_stat("shortest", describe_flight(shortest, context));
+        print_stat(
+            "average",
+            format_elapsed(totals.elapsed / totals.count as i32, context),
+        );
+    }
+
+    let cross_country = summary.cross_country;
+    print_stat(
+        "cross-country",
+        format_args!(
+            "{} flights, {}",
+            cross_country.count,
+            format_elapsed(cross_country.elapsed, context)
+        ),
+    );
+
+    // Flights with an unknown waypoint can't be measured, so they're left out of the total, but
+    // we say how many so the number isn't taken for more than it is.
+    let distance = airport::convert_nm(summary.distance, context.units);
+    match summary.unknown {
+        0 => print_stat("distance", format_args!("{distance:.1} {}", context.units)),
+        unknown => {
+            let flights = if unknown == 1 { "flight" } else { "flights" };
+            print_stat(
+                "distance",
+                format_args!(
+                    "{distance:.1} {} ({unknown} {flights} of unknown distance left out)",
+                    context.units
+                ),
+            );
+        }
+    }
+
+    // Flights we can't measure don't count toward the average, time included.
+    let hours = summary.measured.num_minutes() as f64 / 60.0;
+    if hours > 0.0 {
+        print_stat(
+            "groundspeed",
+            format_speed(summary.distance / hours, context.units),
+        );
+    }
+
+    // Likewise, burn rate and economy only count the flights with fuel logged.
+    let fuel = summary.fuel;
+    if fuel.count > 0 {
+        print_stat(
+            "fuel",
+            describe_fuel(
+                fuel.fuel,
+                (fuel.hours > 0.0).then(|| fuel.fuel / fuel.hours),
+                (fuel.measured_fuel > 0.0).then(|| fuel.distance / fuel.measured_fuel),
+                context,
+            ),
+        );
+    }
+
+    Ok(())
+}
+
+/// Hands `f` each flight logged since `since`, reading the logbook one flight at a time.
+fn read_since(
+    context: &Context,
+    since: Option<DateTime<Utc>>,
+    mut f: impl FnMut(&Flight),
+) -> io::Result<()> {
+    db::for_each_flight(&context.path, context.warn_unreadable(), |flight| {
+        if since.is_none_or(|since| flight.created >= since) {
+            f(&flight);
+        }
+    })
+}
+
+/// Fuel burned, with the burn rate and distance per unit of fuel where they're known.
+fn describe_fuel(
+    fuel: f64,
+    burn_rate: Option<f64>,
+    economy: Option<f64>,
+    context: &Context,
+) -> String {
+    let unit = context.fuel_units;
+    let mut rates = Vec::new();
+    if let Some(burn_rate) = burn_rate {
+        rates.push(format!("{burn_rate:.1} {unit}/hr"));
+    }
+    if let Some(economy) = economy {
+        let economy = airport::convert_nm(economy, context.units);
+        rates.push(format!("{economy:.1} {}/{unit}", context.units));
+    }
+
+    if rates.is_empty() {
+        format!("{fuel:.1} {unit}")
+    } else {
+        format!("{fuel:.1} {unit} ({})", rates.join(", "))
+    }
+}
+
+/// A one-line summary of a flight: how long, when, and where.
+fn describe_flight(flight: &Flight, context: &Context) -> String {
+    format!(
+        "{} ({}, {})",
+        format_elapsed(flight.elapsed, context),
+        format_date(flight.created, context.utc),
+        flight.waypoints.join(" -> ")
+    )
+}
+
+fn print_stat(label: &str, value: impl fmt::Display) {
+    println!("{:<17}{value}", format!("{label}:"));
+}
+
+/// How many airports make the report.
+const REPORT_AIRPORTS: usize = 5;
+
+fn report(args: &ReportArgs, context: &Context) -> io::Result<()> {
+    let year_of = |created: DateTime<Utc>| {
+        if context.utc {
+            created.year()
+        } else {
+            created.with_timezone(&Local).year()
+        }
+    };
+    let year = args.year.unwrap_or_else(|| year_of(Utc::now()));
+
+    let _lock = db::lock(&context.path, LockMode::Shared)?;
+    let mut flights = db::read_flights(&context.path, context.warn_unreadable())?;
+    flights.retain(|flight| year_of(flight.created) == year);
+
+    println!("{year} in review");
+    println!();
+
+    let totals: Totals = flights.iter().collect();
+    print_stat("flights", totals.count);
+    print_stat("total", format_elapsed(totals.elapsed, context));
+
+    // Most people never log a landing count, and a column of assumed ones says nothing.
+    if flights.iter().any(|flight| flight.landings.is_some()) {
+        print_stat("landings", totals.landings);
+    }
+
+    if let Some(longest) = flights.iter().max_by_key(|flight| flight.elapsed) {
+        print_stat("longest", describe_flight(longest, context));
+    }
+
+    if flights.is_empty() {
+        return Ok(());
+    }
+
+    println!();
+    println!("by aircraft");
+    let groups = stats::group_by(&flights, GroupBy::Aircraft, context.utc, false);
+    let width = groups.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    for (aircraft, totals) in groups {
+        let label = if totals.count == 1 {
+            "flight"
+        } else {
+            "flights"
+        };
+        println!(
+            "  {aircraft:<width$}  {:>8}  {:>4} {label}",
+            format_elapsed(totals.elapsed, context),
+            totals.count
+        );
+    }
+
+    println!();
+    println!("top airports");
+    for (airport, count) in stats::airport_visits(&flights)
+        .into_iter()
+        .take(REPORT_AIRPORTS)
+    {
+        println!("  {airport:<8}{count:>5}");
+    }
+
+    Ok(())
+}
+
+fn currency(context: &Context) -> io::Result<()> {
+    let _lock = db::lock(&context.path, LockMode::Shared)?;
+    let flights = db::read_flights(&context.path, context.warn_unreadable())?;
+    let currency = stats::currency(&flights, Utc::now());
+
+    match currency.expires {
+        Some(expires) => {
+            println!(
+                "current: {} landings in the last 90 days",
+                currency.landings
+            );
+            println!("expires: {}", format_date(expires, context.utc));
+        }
+        None => println!(
+            "not current: {} of {} landings in the last 90 days",
+            currency.landings,
+            stats::CURRENCY_LANDINGS
+        ),
+    }
+
+    Ok(())
+}
+
+fn format_date(instant: DateTime<Utc>, utc: bool) -> String {
+    if utc {
+        instant.format("%F").to_string()
+    } else {
+        instant.with_timezone(&Local).format("%F").to_string()
+    }
+}
+
+/// Formats a length of time in hours+minutes, or down to the second with --precise.
+fn format_elapsed(duration: Duration, context: &Context) -> String {
+    if context.precise {
+        match ElapsedTime::try_from(duration) {
+            Ok(elapsed) => format!("{elapsed:#}"),
+            // Only a total could be this long, and nobody needs its seconds.
+            Err(_) => elapsed::format_duration(duration),
+        }
+    } else {
+        elapsed::format_duration(duration)
+    }
+}
+
+fn format_speed(knots: f64, units: DistanceUnit) -> String {
+    format!(
+        "{:.0} {}",
+        airport::convert_nm(knots, units),
+        units.per_hour()
+    )
+}
+
+fn format_distance(waypoints: &[String], units: DistanceUnit) -> String {
+    match airport::route_distance(waypoints) {
+        Some(distance) => format!("{:.1} {units}", airport::convert_nm(distance, units)),
+        None => format!(
+            "distance unknown ({})",
+            airport::unknown_waypoints(waypoints).join(", ")
+        ),
+    }
 }
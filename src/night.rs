@@ -0,0 +1,88 @@
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+
+use crate::airport::{self, Coordinates};
+
+/// The sun's elevation, in degrees, at the end of evening civil twilight and the start of
+/// morning civil twilight. Night is whatever falls between the two.
+const CIVIL_TWILIGHT: f64 = -6.0;
+
+/// Estimates how much of a flight was flown at night, or `None` if any waypoint can't be placed.
+///
+/// The flight is taken to cover its route at a steady pace, a minute at a time, and each minute
+/// counts as night if the sun is below civil twilight where the aircraft would have been. The
+/// sun's position comes from the low-precision NOAA formulas, good to a fraction of a degree,
+/// so expect the answer to be off by a few minutes when the flight crosses twilight.
+pub fn estimate(
+    waypoints: &[String],
+    depart: DateTime<Utc>,
+    elapsed: Duration,
+) -> Option<Duration> {
+    let coordinates = waypoints
+        .iter()
+        .map(|waypoint| airport::lookup(waypoint))
+        .collect::<Option<Vec<_>>>()?;
+
+    let minutes = elapsed.num_minutes();
+    let night = (0..minutes)
+        .filter(|&minute| {
+            // Each minute is judged by its midpoint.
+            let progress = (minute as f64 + 0.5) / minutes as f64;
+            let time = depart + Duration::seconds(minute * 60 + 30);
+            sun_elevation(position(&coordinates, progress), time) < CIVIL_TWILIGHT
+        })
+        .count();
+
+    Some(Duration::minutes(night as i64))
+}
+
+/// Where the aircraft would be after covering `progress` (0 to 1) of the route's distance.
+///
+/// Within a leg the position is interpolated in plain latitude and longitude, which is close
+/// enough for the sun's purposes.
+fn position(coordinates: &[Coordinates], progress: f64) -> Coordinates {
+    let legs: Vec<_> = coordinates
+        .windows(2)
+        .map(|leg| leg[0].distance_to(leg[1]))
+        .collect();
+    let mut remaining = legs.iter().sum::<f64>() * progress;
+
+    for (leg, &length) in coordinates.windows(2).zip(&legs) {
+        if remaining <= length && length > 0.0 {
+            let t = remaining / length;
+            return Coordinates {
+                lat: leg[0].lat + (leg[1].lat - leg[0].lat) * t,
+                lon: leg[0].lon + (leg[1].lon - leg[0].lon) * t,
+            };
+        }
+        remaining -= length;
+    }
+
+    coordinates[coordinates.len() - 1]
+}
+
+/// The sun's elevation above the horizon, in degrees.
+fn sun_elevation(at: Coordinates, time: DateTime<Utc>) -> f64 {
+    let hour = time.hour() as f64 + time.minute() as f64 / 60.0 + time.second() as f64 / 3600.0;
+    let day = time.ordinal0() as f64;
+    let year = std::f64::consts::TAU / 365.0 * (day + (hour - 12.0) / 24.0);
+
+    // Equation of time, in minutes, and declination, in radians.
+    let equation = 229.18
+        * (0.000075 + 0.001868 * year.cos()
+            - 0.032077 * year.sin()
+            - 0.014615 * (2.0 * year).cos()
+            - 0.040849 * (2.0 * year).sin());
+    let declination = 0.006918 - 0.399912 * year.cos() + 0.070257 * year.sin()
+        - 0.006758 * (2.0 * year).cos()
+        + 0.000907 * (2.0 * year).sin()
+        - 0.002697 * (3.0 * year).cos()
+        + 0.00148 * (3.0 * year).sin();
+
+    let solar_minutes = hour * 60.0 + equation + 4.0 * at.lon;
+    let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+
+    let lat = at.lat.to_radians();
+    let cos_zenith =
+        lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos();
+    90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees()
+}
@@ -0,0 +1,266 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    process,
+};
+
+use crate::error::PathContext;
+
+static EDITOR: &str = "hx";
+
+static HELP_MESSAGE: &str = include_str!("../resource/help_message.txt");
+
+/// Past this, a note is more likely a runaway paste than something anyone wrote.
+pub const MAX_LEN: usize = 64 * 1024;
+
+/// Opens an editor for the note on a new flight, starting from the user's own template if they
+/// have one and the bundled help otherwise.
+///
+/// As with the help, saving the template untouched counts as writing nothing.
+pub fn read_new(editor: Option<&str>, template: Option<&str>) -> io::Result<String> {
+    let template = template.unwrap_or(HELP_MESSAGE);
+    open_editor(editor, template, template)
+}
+
+/// Opens an editor for the user to write a note in, starting from `initial`.
+///
+/// The configured editor, if any, wins over `$VISUAL` and `$EDITOR`.
+pub fn read_from_file(editor: Option<&str>, initial: &str) -> io::Result<String> {
+    // Escape any #s already in the note, or they'd be stripped as comments on the way back.
+    let template = initial.replace('#', "\\#") + HELP_MESSAGE;
+    open_editor(editor, &template, HELP_MESSAGE)
+}
+
+/// Opens an editor for an addition to an existing note, which is shown commented out for
+/// reference.
+pub fn read_addition(editor: Option<&str>, existing: &str) -> io::Result<String> {
+    let mut template = String::from(HELP_MESSAGE);
+    template.push_str("#\n# Existing notes:\n#\n");
+    for line in existing.lines() {
+        template.push_str("#   ");
+        template.push_str(line);
+        template.push('\n');
+    }
+    open_editor(editor, &template, HELP_MESSAGE)
+}
+
+/// Has the user edit `template`, then returns what they wrote. Anything that strips down to the
+/// same thing as `blank` is taken as no note at all.
+fn open_editor(editor: Option<&str>, template: &str, blank: &str) -> io::Result<String> {
+    // The .md extension gets most editors into a mode suited to prose, and the pid keeps two
+    // instances from writing over each other's notes.
+    let path = env::temp_dir().join(format!("route-note-{}.md", process::id()));
+
+    let editor = editor.map_or_else(default_editor, String::from);
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(EDITOR);
+
+    // Better to find out now than after the note's been written out to a file nobody will open.
+    if !is_installed(program) {
+        return Err(editor_not_found(program));
+    }
+
+    write_private(&path, template).with_path("write", &path)?;
+    let _cleanup = RemoveOnDrop(&path);
+    let status = match process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(editor_not_found(program)),
+        Err(e) => return Err(e).with_path("run editor", Path::new(program)),
+    };
+
+    // If the editor bailed, whatever's in the file is suspect; better to abort the whole entry
+    // than to save a flight with garbage notes.
+    if !status.success() {
+        return Err(io::Error::other(format!("editor exited with {status}")));
+    }
+
+    let notes = strip_comments(fs::read_to_string(&path).with_path("read", &path)?);
+
+    // Quitting without saving leaves only the template behind, which isn't a note.
+    if notes.trim().is_empty() || notes == strip_comments(blank.into()) {
+        return Ok(String::new());
+    }
+
+    Ok(notes.trim_matches('\n').into())
+}
+
+/// Whether `program` names something we could run, either by path or by a search of `$PATH`.
+fn is_installed(program: &str) -> bool {
+    // Windows will run foo.exe for foo; elsewhere, the extension list is just the bare name.
+    let extensions: &[&str] = if cfg!(windows) {
+        &["", ".exe", ".cmd", ".bat"]
+    } else {
+        &[""]
+    };
+    let exists = |path: &Path| {
+        extensions.iter().any(|ext| {
+            let mut name = path.as_os_str().to_owned();
+            name.push(ext);
+            Path::new(&name).is_file()
+        })
+    };
+
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return exists(path);
+    }
+
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| exists(&dir.join(path))))
+}
+
+fn editor_not_found(program: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("editor '{program}' not found; set $EDITOR or `editor` in the config file"),
+    )
+}
+
+/// Notes can be personal, so the temp file is readable only by its owner where we can manage
+/// that.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// Deletes the temp file however we leave `open_editor`, so notes don't linger in the temp
+/// directory.
+struct RemoveOnDrop<'a>(&'a Path);
+
+impl Drop for RemoveOnDrop<'_> {
+    fn drop(&mut self) {
+        // Some editors save by renaming over the original, and a few may remove it on exit; if
+        // it's gone already, so much the better.
+        if let Err(e) = fs::remove_file(self.0) {
+            if e.kind() != io::ErrorKind::NotFound {
+                eprintln!("warning: failed to remove {}: {e}", self.0.display());
+            }
+        }
+    }
+}
+
+/// Reads a note piped in on stdin, e.g. `echo "..." | route ... --notes -`.
+pub fn read_from_stdin() -> io::Result<String> {
+    let notes = io::read_to_string(io::stdin())?;
+    Ok(strip_comments(notes).trim_matches('\n').into())
+}
+
+/// Reads a note kept in a file of the user's own.
+pub fn read_from_path(path: &Path) -> io::Result<String> {
+    let notes = fs::read_to_string(path).with_path("read", path)?;
+    Ok(strip_comments(notes).trim_matches('\n').into())
+}
+
+/// Joins notes given with `--notes`, one line for each time it was passed.
+pub fn from_args(notes: &[String]) -> String {
+    strip_comments(notes.join("\n")).trim_matches('\n').into()
+}
+
+/// Picks the user's editor from `$VISUAL` or `$EDITOR`, falling back to helix.
+///
+/// The value may carry arguments, e.g. `EDITOR="code --wait"`.
+fn default_editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| EDITOR.into())
+}
+
+/// Removes comments from a note.
+///
+/// Lines whose first non-whitespace character is `#` are dropped entirely; elsewhere, an
+/// unescaped `#` comments out the rest of its line. Write `\#` for a literal `#`.
+///
+/// Whatever line endings the editor used, the result uses `\n`.
+pub fn strip_comments(notes: String) -> String {
+    // Some Windows editors like to open with a byte order mark, which would otherwise hide a
+    // comment on the first line.
+    let notes = notes.strip_prefix('\u{feff}').unwrap_or(&notes);
+    let mut buf = String::with_capacity(notes.len());
+
+    for line in notes.lines() {
+        // lines() eats \r\n, but not a stray \r at the very end of the file.
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if !line.trim_start().starts_with('#') {
+            push_uncommented(&mut buf, line);
+            buf.push('\n');
+        }
+    }
+
+    // If it sucks but it works, it... still sucks.
+    if buf.ends_with('\n') {
+        buf.truncate(buf.len() - 1);
+    }
+
+    buf
+}
+
+fn push_uncommented(buf: &mut String, line: &str) {
+    let start = buf.len();
+    let mut chars = line.chars().peekable();
+
+    while let Some(u) = chars.next() {
+        match u {
+            '\\' if chars.peek() == Some(&'#') => {
+                buf.push('#');
+                chars.next();
+            }
+            '#' => {
+                // Don't leave the space that separated the comment from the note.
+                let trimmed = buf[start..].trim_end().len();
+                buf.truncate(start + trimmed);
+                return;
+            }
+            u => buf.push(u),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(notes: &str) -> String {
+        strip_comments(notes.to_string())
+    }
+
+    #[test]
+    fn crlf_comes_out_as_lf() {
+        assert_eq!(strip("# help\r\nfirst\r\nsecond\r\n"), "first\nsecond");
+    }
+
+    #[test]
+    fn leading_bom_doesnt_hide_a_comment() {
+        assert_eq!(strip("\u{feff}# help\nnote"), "note");
+    }
+
+    #[test]
+    fn indented_comment_is_dropped() {
+        assert_eq!(strip("note\n   # help\n\t# more help"), "note");
+    }
+
+    #[test]
+    fn trailing_lone_cr_is_dropped() {
+        assert_eq!(strip("note\r"), "note");
+    }
+
+    #[test]
+    fn escaped_hash_is_kept() {
+        assert_eq!(strip("runway 16\\#L # the long one"), "runway 16#L");
+    }
+}
@@ -0,0 +1,118 @@
+use crate::airport::{self, Coordinates};
+
+const WIDTH: usize = 40;
+const HEIGHT: usize = 12;
+
+/// Marks waypoints on the grid in route order.
+const MARKERS: &[u8] = b"123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Draws the route in a box, with a legend naming each marker, or `None` if any waypoint has no
+/// known coordinates.
+///
+/// This is not cartography: points are placed by latitude and longitude (with longitude squeezed
+/// by the cosine of the mean latitude) and joined with straight lines. It's good for spotting a
+/// route that doubles back where it shouldn't, and not much else.
+pub fn render(waypoints: &[String]) -> Option<String> {
+    let coordinates = waypoints
+        .iter()
+        .map(|waypoint| airport::lookup(waypoint))
+        .collect::<Option<Vec<_>>>()?;
+    let cells = place(&coordinates);
+
+    let mut grid = vec![[b' '; WIDTH]; HEIGHT];
+    for pair in cells.windows(2) {
+        draw_line(&mut grid, pair[0], pair[1]);
+    }
+
+    // Markers go on last so lines never cover them. Past the 35th, points just get a `*`.
+    for (idx, &(col, row)) in cells.iter().enumerate() {
+        grid[row][col] = MARKERS.get(idx).copied().unwrap_or(b'*');
+    }
+
+    let border = format!("+{}+\n", "-".repeat(WIDTH));
+    let mut sketch = border.clone();
+    for row in &grid {
+        sketch.push('|');
+        sketch.push_str(&String::from_utf8_lossy(row));
+        sketch.push_str("|\n");
+    }
+    sketch.push_str(&border);
+
+    let legend: Vec<_> = waypoints
+        .iter()
+        .zip(MARKERS)
+        .map(|(waypoint, &marker)| format!("{} {waypoint}", marker as char))
+        .collect();
+    sketch.push_str(&legend.join("  "));
+
+    Some(sketch)
+}
+
+/// Maps coordinates to (column, row) cells, centering the route and keeping its proportions.
+fn place(coordinates: &[Coordinates]) -> Vec<(usize, usize)> {
+    let mean_lat = coordinates.iter().map(|c| c.lat).sum::<f64>() / coordinates.len().max(1) as f64;
+    let squeeze = mean_lat.to_radians().cos();
+    let points: Vec<_> = coordinates
+        .iter()
+        .map(|c| (c.lon * squeeze, c.lat))
+        .collect();
+
+    let (min_x, max_x) = bounds(points.iter().map(|p| p.0));
+    let (min_y, max_y) = bounds(points.iter().map(|p| p.1));
+
+    // A character cell is about twice as tall as it is wide, hence two columns per row.
+    let span_x = (max_x - min_x) * 2.0;
+    let span_y = max_y - min_y;
+    let scale = match (span_x > 0.0, span_y > 0.0) {
+        (true, true) => f64::min((WIDTH - 1) as f64 / span_x, (HEIGHT - 1) as f64 / span_y),
+        (true, false) => (WIDTH - 1) as f64 / span_x,
+        (false, true) => (HEIGHT - 1) as f64 / span_y,
+        (false, false) => 0.0,
+    };
+
+    let offset_x = ((WIDTH - 1) as f64 - span_x * scale) / 2.0;
+    let offset_y = ((HEIGHT - 1) as f64 - span_y * scale) / 2.0;
+
+    points
+        .iter()
+        .map(|&(x, y)| {
+            let col = offset_x + (x - min_x) * 2.0 * scale;
+            let row = offset_y + (max_y - y) * scale;
+            (col.round() as usize, row.round() as usize)
+        })
+        .collect()
+}
+
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    })
+}
+
+fn draw_line(grid: &mut [[u8; WIDTH]], from: (usize, usize), to: (usize, usize)) {
+    let (x0, y0) = (from.0 as isize, from.1 as isize);
+    let (x1, y1) = (to.0 as isize, to.1 as isize);
+    let (dx, dy) = (x1 - x0, y1 - y0);
+
+    let stroke = if dx.abs() > 2 * dy.abs() {
+        b'-'
+    } else if 2 * dx.abs() < dy.abs() {
+        b'|'
+    } else if (dx > 0) == (dy > 0) {
+        b'\\'
+    } else {
+        b'/'
+    };
+
+    let steps = dx.abs().max(dy.abs());
+    for step in 0..=steps {
+        let t = if steps == 0 {
+            0.0
+        } else {
+            step as f64 / steps as f64
+        };
+        let col = (x0 as f64 + dx as f64 * t).round() as usize;
+        let row = (y0 as f64 + dy as f64 * t).round() as usize;
+        grid[row][col] = stroke;
+    }
+}
@@ -0,0 +1,237 @@
+//! The SQLite backend, for logbooks that have outgrown a file of JSON lines.
+//!
+//! Flights live in a single `flights` table, one column per field. Lists (waypoints and tags) are
+//! stored as JSON arrays, since nothing ever queries inside them. Every write happens in a
+//! transaction, so there's no such thing as a half-written record here.
+
+use std::{io, path::Path};
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use rusqlite::{params, Connection, Row};
+
+use crate::{
+    db::{self, Corrupt},
+    error::PathContext,
+    flight::{Flight, CURRENT_VERSION},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS flights (
+        id TEXT PRIMARY KEY,
+        created TEXT NOT NULL,
+        waypoints TEXT NOT NULL,
+        elapsed INTEGER NOT NULL,
+        depart TEXT,
+        arrive TEXT,
+        night INTEGER,
+        instrument INTEGER,
+        aircraft TEXT,
+        landings INTEGER,
+        fuel REAL,
+        tags TEXT NOT NULL,
+        simulator INTEGER NOT NULL,
+        notes TEXT
+    );
+    CREATE INDEX IF NOT EXISTS flights_created ON flights (created);
+";
+
+/// Tells a SQLite logbook from a JSON one by its extension.
+pub fn is_sqlite(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "sqlite")
+}
+
+fn open(path: &Path) -> io::Result<Connection> {
+    let connection = Connection::open(path)
+        .map_err(io::Error::other)
+        .with_path("open", path)?;
+    connection
+        .execute_batch(SCHEMA)
+        .map_err(io::Error::other)
+        .with_path("set up", path)?;
+    Ok(connection)
+}
+
+/// Hands every flight to `f`, oldest first, one row at a time.
+pub fn for_each_flight(path: &Path, mut f: impl FnMut(Flight)) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let connection = open(path)?;
+    connection
+        .prepare("SELECT * FROM flights ORDER BY created, id")
+        .and_then(|mut statement| {
+            for flight in statement.query_map([], from_row)? {
+                f(flight?);
+            }
+            Ok(())
+        })
+        .map_err(io::Error::other)
+        .with_path("read", path)
+}
+
+/// Adds flights to the table.
+pub fn append_flights<'a>(
+    path: &Path,
+    flights: impl IntoIterator<Item = &'a Flight>,
+) -> io::Result<()> {
+    let mut connection = open(path)?;
+    insert(&mut connection, flights, false).with_path("write", path)
+}
+
+/// Replaces every flight in the table with the given ones.
+pub fn write_flights<'a>(
+    path: &Path,
+    flights: impl IntoIterator<Item = &'a Flight>,
+) -> io::Result<()> {
+    let mut connection = open(path)?;
+    insert(&mut connection, flights, true).with_path("write", path)
+}
+
+fn insert<'a>(
+    connection: &mut Connection,
+    flights: impl IntoIterator<Item = &'a Flight>,
+    replace: bool,
+) -> io::Result<()> {
+    let transaction = connection.transaction().map_err(io::Error::other)?;
+    if replace {
+        transaction
+            .execute("DELETE FROM flights", [])
+            .map_err(io::Error::other)?;
+    }
+
+    {
+        let mut statement = transaction
+            .prepare("INSERT INTO flights VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            .map_err(io::Error::other)?;
+        for flight in flights {
+            statement
+                .execute(params![
+                    flight.id,
+                    flight.created.to_rfc3339(),
+                    serde_json::to_string(&flight.waypoints)?,
+                    flight.elapsed.num_seconds(),
+                    flight.depart.map(|time| time.to_string()),
+                    flight.arrive.map(|time| time.to_string()),
+                    flight.night.map(|night| night.num_seconds()),
+                    flight.instrument.map(|instrument| instrument.num_seconds()),
+                    flight.aircraft,
+                    flight.landings,
+                    flight.fuel,
+                    serde_json::to_string(&flight.tags)?,
+                    flight.simulator,
+                    flight.notes,
+                ])
+                .map_err(io::Error::other)?;
+        }
+    }
+
+    transaction.commit().map_err(io::Error::other)
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<Flight> {
+    let created: String = row.get("created")?;
+    let waypoints: String = row.get("waypoints")?;
+    let depart: Option<String> = row.get("depart")?;
+    let arrive: Option<String> = row.get("arrive")?;
+    let tags: String = row.get("tags")?;
+
+    Ok(Flight {
+        version: CURRENT_VERSION,
+        id: row.get("id")?,
+        created: DateTime::parse_from_rfc3339(&created)
+            .map_err(|e| invalid(e.into()))?
+            .with_timezone(&Utc),
+        waypoints: serde_json::from_str(&waypoints).map_err(|e| invalid(e.into()))?,
+        elapsed: Duration::seconds(row.get("elapsed")?),
+        depart: depart.as_deref().map(parse_time).transpose()?,
+        arrive: arrive.as_deref().map(parse_time).transpose()?,
+        night: row.get::<_, Option<i64>>("night")?.map(Duration::seconds),
+        instrument: row
+            .get::<_, Option<i64>>("instrument")?
+            .map(Duration::seconds),
+        aircraft: row.get("aircraft")?,
+        landings: row.get("landings")?,
+        fuel: row.get("fuel")?,
+        tags: serde_json::from_str(&tags).map_err(|e| invalid(e.into()))?,
+        simulator: row.get("simulator")?,
+        notes: row.get("notes")?,
+    })
+}
+
+fn parse_time(time: &str) -> rusqlite::Result<NaiveTime> {
+    time.parse()
+        .map_err(|e: chrono::ParseError| invalid(e.into()))
+}
+
+fn invalid(e: Box<dyn std::error::Error + Send + Sync>) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e)
+}
+
+/// Checks every row, as `db::verify` checks every line.
+pub fn verify(path: &Path) -> io::Result<(usize, Vec<Corrupt>)> {
+    let connection = open(path)?;
+    let mut valid = 0;
+    let mut corrupt = Vec::new();
+    connection
+        .prepare("SELECT * FROM flights ORDER BY created, id")
+        .and_then(|mut statement| {
+            for (idx, flight) in statement.query_map([], from_row)?.enumerate() {
+                let reason = match flight {
+                    Err(e) => Some(format!("unreadable ({e})")),
+                    Ok(flight) => db::implausible(&flight),
+                };
+                match reason {
+                    Some(reason) => corrupt.push(Corrupt {
+                        line: idx + 1,
+                        reason,
+                    }),
+                    None => valid += 1,
+                }
+            }
+            Ok(())
+        })
+        .map_err(io::Error::other)
+        .with_path("read", path)?;
+
+    Ok((valid, corrupt))
+}
+
+/// Copies the database to `destination` with `VACUUM INTO`, returning the number of flights in
+/// the copy. As with a JSON backup, a copy that doesn't hold every row is deleted.
+pub fn backup(path: &Path, destination: &Path) -> io::Result<usize> {
+    let connection = open(path)?;
+    connection
+        .execute("VACUUM INTO ?1", [destination.to_string_lossy()])
+        .map_err(io::Error::other)
+        .with_path("copy to", destination)?;
+
+    let checked = count(&connection)
+        .and_then(|expected| Ok((expected, count(&open(destination)?)?)))
+        .and_then(|(expected, copied)| {
+            if copied == expected {
+                Ok(copied)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "backup {} doesn't match the logbook ({copied} of {expected} flights)",
+                        destination.display()
+                    ),
+                ))
+            }
+        });
+    if checked.is_err() {
+        let _ = std::fs::remove_file(destination);
+    }
+    checked
+}
+
+fn count(connection: &Connection) -> io::Result<usize> {
+    connection
+        .query_row("SELECT count(*) FROM flights", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map(|count| count as usize)
+        .map_err(io::Error::other)
+}
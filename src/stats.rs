@@ -0,0 +1,344 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, Utc};
+
+use clap::ValueEnum;
+
+use crate::{airport, flight::Flight};
+
+/// The ways `group_by` can break totals down.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum GroupBy {
+    Month,
+    Year,
+    Aircraft,
+    Route,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Totals {
+    pub count: usize,
+    pub elapsed: Duration,
+    pub landings: u32,
+}
+
+impl Totals {
+    pub fn add(&mut self, flight: &Flight) {
+        self.count += 1;
+        self.elapsed += flight.elapsed;
+        self.landings += flight.landing_count();
+    }
+}
+
+impl<'a> FromIterator<&'a Flight> for Totals {
+    fn from_iter<I: IntoIterator<Item = &'a Flight>>(flights: I) -> Self {
+        let mut totals = Totals::default();
+        for flight in flights {
+            totals.add(flight);
+        }
+        totals
+    }
+}
+
+/// Everything `stats` reports on by default, built up one flight at a time so the logbook never
+/// has to be held in memory.
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub totals: Totals,
+    pub simulator: Totals,
+    pub cross_country: Totals,
+    pub longest: Option<Flight>,
+    pub shortest: Option<Flight>,
+    /// Distance in nautical miles, over the flights that could be measured.
+    pub distance: f64,
+    /// Time spent on the flights that could be measured.
+    pub measured: Duration,
+    /// Flights with a waypoint we couldn't place.
+    pub unknown: usize,
+    pub fuel: FuelTotals,
+}
+
+/// Fuel logged, and what it can be set against: the time of every flight that logged fuel, and
+/// the distance of those that could also be measured.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FuelTotals {
+    pub count: usize,
+    pub fuel: f64,
+    pub hours: f64,
+    pub distance: f64,
+    pub measured_fuel: f64,
+}
+
+impl Summary {
+    pub fn add(&mut self, flight: &Flight) {
+        self.totals.add(flight);
+        if flight.simulator {
+            self.simulator.add(flight);
+        }
+        if flight.is_cross_country() == Some(true) {
+            self.cross_country.add(flight);
+        }
+
+        // Ties go to the latest longest flight and the earliest shortest, as max_by_key and
+        // min_by_key would have it.
+        if self
+            .longest
+            .as_ref()
+            .is_none_or(|longest| flight.elapsed >= longest.elapsed)
+        {
+            self.longest = Some(flight.clone());
+        }
+        if self
+            .shortest
+            .as_ref()
+            .is_none_or(|shortest| flight.elapsed < shortest.elapsed)
+        {
+            self.shortest = Some(flight.clone());
+        }
+
+        let distance = airport::route_distance(&flight.waypoints);
+        match distance {
+            Some(distance) => {
+                self.distance += distance;
+                self.measured += flight.elapsed;
+            }
+            None => self.unknown += 1,
+        }
+
+        if let Some(fuel) = flight.fuel {
+            self.fuel.count += 1;
+            self.fuel.fuel += fuel;
+            self.fuel.hours += flight.elapsed.num_minutes() as f64 / 60.0;
+            if let Some(distance) = distance {
+                self.fuel.distance += distance;
+                self.fuel.measured_fuel += fuel;
+            }
+        }
+    }
+}
+
+/// Takeoffs and landings required to carry passengers (14 CFR 61.57(a)).
+pub const CURRENCY_LANDINGS: u32 = 3;
+
+/// The window in which those landings must fall.
+pub fn currency_window() -> Duration {
+    Duration::days(90)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Currency {
+    /// Landings within the current window.
+    pub landings: u32,
+    /// When currency lapses, or `None` if the pilot isn't current now.
+    pub expires: Option<DateTime<Utc>>,
+}
+
+/// Works out passenger-carrying currency as of `now`.
+///
+/// Currency lasts until the most recent three landings are no longer all inside the window, so
+/// it expires one window's length after the flight that brought the count to three.
+pub fn currency(flights: &[Flight], now: DateTime<Utc>) -> Currency {
+    let start = now - currency_window();
+
+    let mut recent: Vec<_> = flights
+        .iter()
+        .filter(|flight| flight.created > start && flight.created <= now)
+        .collect();
+    recent.sort_by_key(|flight| Reverse(flight.created));
+
+    let landings = recent.iter().map(|flight| flight.landing_count()).sum();
+
+    let mut count = 0;
+    let expires = recent.iter().find_map(|flight| {
+        count += flight.landing_count();
+        (count >= CURRENCY_LANDINGS).then(|| flight.created + currency_window())
+    });
+
+    Currency { landings, expires }
+}
+
+/// Counts appearances of each waypoint across all flights, most visited first.
+pub fn airport_visits<'a>(flights: impl IntoIterator<Item = &'a Flight>) -> Vec<(&'a str, usize)> {
+    let mut visits: HashMap<&str, usize> = HashMap::new();
+    for waypoint in flights.into_iter().flat_map(|flight| &flight.waypoints) {
+        *visits.entry(waypoint).or_default() += 1;
+    }
+    most_visited(visits)
+}
+
+/// `airport_visits`, one flight at a time.
+#[derive(Clone, Debug, Default)]
+pub struct Visits(HashMap<String, usize>);
+
+impl Visits {
+    pub fn add(&mut self, flight: &Flight) {
+        for waypoint in &flight.waypoints {
+            *self.0.entry(waypoint.clone()).or_default() += 1;
+        }
+    }
+
+    pub fn finish(self) -> Vec<(String, usize)> {
+        most_visited(self.0)
+    }
+}
+
+fn most_visited<K: Ord>(visits: HashMap<K, usize>) -> Vec<(K, usize)> {
+    let mut visits: Vec<_> = visits.into_iter().collect();
+    visits.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    visits
+}
+
+/// Totals flights by period, aircraft, or route.
+///
+/// Periods are keyed as `YYYY` or `YYYY-MM` and come back in chronological order. Aircraft come
+/// back with the most time first, and routes with the most flights first. Routes are keyed on
+/// their two ends; with `bidirectional`, a route and its reverse count as one.
+pub fn group_by<'a>(
+    flights: impl IntoIterator<Item = &'a Flight>,
+    by: GroupBy,
+    utc: bool,
+    bidirectional: bool,
+) -> Vec<(String, Totals)> {
+    let mut groups = Groups::new(by, utc, bidirectional);
+    for flight in flights {
+        groups.add(flight);
+    }
+    groups.finish()
+}
+
+/// `group_by`, one flight at a time.
+#[derive(Clone, Debug)]
+pub struct Groups {
+    by: GroupBy,
+    utc: bool,
+    bidirectional: bool,
+    // A BTreeMap keeps things in key order, which for periods is already what we want.
+    groups: BTreeMap<String, Totals>,
+}
+
+impl Groups {
+    pub fn new(by: GroupBy, utc: bool, bidirectional: bool) -> Self {
+        Self {
+            by,
+            utc,
+            bidirectional,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, flight: &Flight) {
+        let key = match self.by {
+            GroupBy::Month | GroupBy::Year => period_key(flight.created, self.by, self.utc),
+            GroupBy::Aircraft => flight
+                .aircraft
+                .clone()
+                .unwrap_or_else(|| String::from("(unspecified)")),
+            GroupBy::Route => route_key(flight, self.bidirectional),
+        };
+        self.groups.entry(key).or_default().add(flight);
+    }
+
+    pub fn finish(self) -> Vec<(String, Totals)> {
+        let mut groups: Vec<_> = self.groups.into_iter().collect();
+        match self.by {
+            GroupBy::Month | GroupBy::Year => (),
+            GroupBy::Aircraft => groups.sort_by_key(|(_, totals)| Reverse(totals.elapsed)),
+            GroupBy::Route => groups.sort_by_key(|(_, totals)| Reverse(totals.count)),
+        }
+        groups
+    }
+}
+
+/// The keys `group_by` would give the last `count` months, oldest first, ending with the month
+/// containing `now`.
+pub fn recent_months(now: DateTime<Utc>, count: u32, utc: bool) -> Vec<String> {
+    // The months are counted on the calendar the keys are written in. Stepping back from an
+    // instant instead lands in the wrong month whenever the local date is already a day ahead.
+    let today = if utc {
+        now.date_naive()
+    } else {
+        now.with_timezone(&Local).date_naive()
+    };
+    months_ending(today, count)
+}
+
+/// The `count` months ending with the one containing `today`, as `YYYY-MM`.
+fn months_ending(today: NaiveDate, count: u32) -> Vec<String> {
+    let first = today.with_day(1).expect("every month has a first day");
+    (0..count)
+        .rev()
+        .filter_map(|back| first.checked_sub_months(Months::new(back)))
+        .map(|month| month.format("%Y-%m").to_string())
+        .collect()
+}
+
+/// Draws one block character per value, scaled so the largest is a full block. Nothing at all is
+/// left blank, so that an idle month can't be mistaken for a slow one.
+pub fn sparkline(values: &[Duration]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let max = values.iter().max().copied().unwrap_or_default();
+    values
+        .iter()
+        .map(|value| {
+            if value.is_zero() {
+                return ' ';
+            }
+            let level = value.num_seconds() as f64 / max.num_seconds() as f64;
+            let idx = (level * BLOCKS.len() as f64).ceil() as usize;
+            BLOCKS[idx.clamp(1, BLOCKS.len()) - 1]
+        })
+        .collect()
+}
+
+fn route_key(flight: &Flight, bidirectional: bool) -> String {
+    let origin = flight.waypoints.first().map(String::as_str).unwrap_or("?");
+    let destination = flight.waypoints.last().map(String::as_str).unwrap_or("?");
+
+    if bidirectional {
+        let (a, b) = if origin <= destination {
+            (origin, destination)
+        } else {
+            (destination, origin)
+        };
+        format!("{a} <-> {b}")
+    } else {
+        format!("{origin} -> {destination}")
+    }
+}
+
+fn period_key(created: DateTime<Utc>, by: GroupBy, utc: bool) -> String {
+    let format = match by {
+        GroupBy::Month => "%Y-%m",
+        GroupBy::Year => "%Y",
+        GroupBy::Aircraft | GroupBy::Route => unreachable!(),
+    };
+
+    if utc {
+        created.format(format).to_string()
+    } else {
+        created.with_timezone(&Local).format(format).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_months_step_back_from_the_first() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(
+            months_ending(date(2026, 3, 31), 3),
+            ["2026-01", "2026-02", "2026-03"]
+        );
+        assert_eq!(
+            months_ending(date(2026, 1, 30), 3),
+            ["2025-11", "2025-12", "2026-01"]
+        );
+        assert!(months_ending(date(2026, 1, 30), 0).is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+use std::{env, io::IsTerminal};
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Space between columns.
+const GAP: usize = 2;
+
+pub const HEADERS: [&str; 5] = ["date", "route", "time", "distance", "aircraft"];
+
+/// One flight's line in the table, plus what goes underneath it.
+pub struct Row {
+    pub cells: [String; 5],
+    pub id: String,
+    pub notes: Option<String>,
+}
+
+/// Whether to color output, which takes a terminal, no `--no-color`, and no `NO_COLOR`.
+///
+/// Per no-color.org, `NO_COLOR` counts only when it's set to something.
+pub fn use_color(no_color: bool) -> bool {
+    !no_color
+        && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        && std::io::stdout().is_terminal()
+}
+
+/// The terminal's width, as far as we can tell without asking it.
+pub fn width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
+
+pub fn print(rows: &[Row], color: bool, width: usize) {
+    let mut widths = HEADERS.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(&row.cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    // Padding has to happen before the escape codes go on, or they'd count toward the width.
+    let line = |cells: [&str; 5], styles: [Option<&str>; 5]| {
+        let mut line = String::new();
+        for (idx, cell) in cells.iter().enumerate() {
+            let padded = if idx + 1 == cells.len() {
+                cell.to_string()
+            } else {
+                format!("{cell:<0$}", widths[idx] + GAP)
+            };
+            line.push_str(&paint(&padded, styles[idx], color));
+        }
+        line.trim_end().to_string()
+    };
+
+    println!("{}", line(HEADERS, [Some(BOLD); 5]));
+    for row in rows {
+        let cells = row.cells.each_ref().map(String::as_str);
+        println!("{}", line(cells, [Some(DIM), Some(BOLD), None, None, None]));
+
+        // Details sit under the route, wrapped to whatever room is left.
+        let indent = " ".repeat(widths[0] + GAP);
+        let room = width.saturating_sub(indent.len()).max(20);
+        println!("{indent}{}", paint(&row.id, Some(DIM), color));
+        if let Some(notes) = &row.notes {
+            for line in wrap(notes, room) {
+                println!("{indent}{line}");
+            }
+        }
+    }
+}
+
+fn paint(text: &str, style: Option<&str>, color: bool) -> String {
+    match style {
+        Some(style) if color && !text.trim().is_empty() => format!("{style}{text}{RESET}"),
+        _ => text.to_string(),
+    }
+}
+
+/// Breaks text into lines no wider than `width`, at spaces where possible. Line breaks already in
+/// the text are kept.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,47 @@
+use route::{elapsed::ElapsedTime, export, flight::Flight, import};
+
+#[test]
+fn export_then_import_round_trips() {
+    let mut plain = Flight::new("KSEA", ["KPDX"], "1+10".parse::<ElapsedTime>().unwrap());
+    plain.add_aircraft("N12345");
+
+    // Quotes, commas, and line breaks all have to survive the trip, as do coordinates with their
+    // minus signs.
+    let mut awkward = Flight::new(
+        "47.45,-122.31",
+        ["-33.9,151.2"],
+        "95".parse::<ElapsedTime>().unwrap(),
+    );
+    awkward.add_notes("said \"hello\", then\nsaid goodbye");
+
+    let mut csv = Vec::new();
+    export::write_csv(&mut csv, [&plain, &awkward]).unwrap();
+    let imported = import::read_csv(&String::from_utf8(csv).unwrap()).unwrap();
+
+    assert!(imported.skipped.is_empty());
+    assert_eq!(imported.flights.len(), 2);
+    for (original, imported) in [&plain, &awkward].into_iter().zip(&imported.flights) {
+        assert_eq!(original.waypoints, imported.waypoints);
+        assert_eq!(original.elapsed, imported.elapsed);
+        assert_eq!(original.aircraft, imported.aircraft);
+        assert_eq!(original.notes, imported.notes);
+        assert_eq!(original.created.timestamp(), imported.created.timestamp());
+    }
+}
+
+#[test]
+fn bad_rows_are_skipped_by_line() {
+    let csv = format!(
+        "{}\n2024-01-01T00:00:00Z,KSEA-KPDX,70,,\nnot a date,KSEA-KPDX,70,,\n2024-01-02T00:00:00Z,KSEA,70,,\n",
+        export::CSV_HEADER
+    );
+    let imported = import::read_csv(&csv).unwrap();
+    assert_eq!(imported.flights.len(), 1);
+    assert_eq!(imported.lines, [2]);
+    assert_eq!(imported.skipped, [3, 4]);
+}
+
+#[test]
+fn wrong_header_is_fatal() {
+    assert!(import::read_csv("date,route,time\n").is_err());
+}
@@ -0,0 +1,180 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use route::{db, elapsed::ElapsedTime, flight::Flight};
+
+/// A logbook path of its own for each test, cleared of anything an earlier run left behind.
+fn logbook(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("route-test-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("db.json")
+}
+
+fn flight(origin: &str, destination: &str, minutes: &str) -> Flight {
+    Flight::new(
+        origin,
+        [destination],
+        minutes.parse::<ElapsedTime>().unwrap(),
+    )
+}
+
+fn routes(path: &Path) -> Vec<String> {
+    db::read_flights(path, false)
+        .unwrap()
+        .iter()
+        .map(|flight| flight.waypoints.join("-"))
+        .collect()
+}
+
+#[test]
+fn appended_flights_read_back_in_order() {
+    let path = logbook("append");
+    db::append_flights(&path, &[flight("KSEA", "KPDX", "70")]).unwrap();
+    db::append_flights(&path, &[flight("KPDX", "KBFI", "55")]).unwrap();
+
+    assert_eq!(routes(&path), ["KSEA-KPDX", "KPDX-KBFI"]);
+    let last = db::read_last(&path, false).unwrap().unwrap();
+    assert_eq!(last.waypoints, ["KPDX", "KBFI"]);
+}
+
+#[test]
+fn missing_logbook_reads_as_empty() {
+    let path = logbook("missing");
+    assert!(db::read_flights(&path, false).unwrap().is_empty());
+    assert!(db::read_last(&path, false).unwrap().is_none());
+}
+
+#[test]
+fn corrupt_lines_are_skipped_and_kept() {
+    let path = logbook("corrupt");
+    db::append_flights(&path, &[flight("KSEA", "KPDX", "70")]).unwrap();
+    let mut text = fs::read_to_string(&path).unwrap();
+    text.push_str("{\"created\": \"2024-01\n\n");
+    fs::write(&path, &text).unwrap();
+    db::append_flights(&path, &[flight("KPDX", "KBFI", "55")]).unwrap();
+
+    let (flights, unreadable) = db::read_entries(&path).unwrap();
+    assert_eq!(flights.len(), 2);
+    assert_eq!(unreadable.len(), 1);
+    assert_eq!(unreadable[0].line, 2);
+
+    // Rewriting the log keeps the unreadable line for the user to deal with.
+    db::write_entries(&path, &flights, &unreadable).unwrap();
+    let (_, unreadable) = db::read_entries(&path).unwrap();
+    assert_eq!(unreadable.len(), 1);
+
+    let (valid, corrupt) = db::verify(&path).unwrap();
+    assert_eq!((valid, corrupt.len()), (2, 1));
+}
+
+#[test]
+fn version_1_records_get_a_stable_id() {
+    let path = logbook("migrate");
+    let record = r#"{"created":"2022-10-01T12:00:00Z","waypoints":["KSEA","KPDX"],"elapsed":4200}"#;
+    fs::write(&path, format!("{record}\n")).unwrap();
+
+    let first = db::read_flights(&path, false).unwrap();
+    let second = db::read_flights(&path, false).unwrap();
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].version, route::flight::CURRENT_VERSION);
+    assert_eq!(first[0].id, second[0].id);
+    assert_eq!(first[0].elapsed.num_minutes(), 70);
+}
+
+#[test]
+fn newer_versions_are_unreadable() {
+    let path = logbook("future");
+    let record = r#"{"version":99,"id":"x","created":"2022-10-01T12:00:00Z","waypoints":["KSEA","KPDX"],"elapsed":60}"#;
+    fs::write(&path, format!("{record}\n")).unwrap();
+
+    let (flights, unreadable) = db::read_entries(&path).unwrap();
+    assert!(flights.is_empty());
+    assert_eq!(unreadable.len(), 1);
+}
+
+#[test]
+fn backup_copies_unreadable_lines_too() {
+    let path = logbook("backup");
+    db::append_flights(&path, &[flight("KSEA", "KPDX", "70")]).unwrap();
+    let mut text = fs::read_to_string(&path).unwrap();
+    text.push_str("{\"created\": \"2024-01\n");
+    fs::write(&path, &text).unwrap();
+
+    let destination = path.with_file_name("backup.json");
+    assert_eq!(db::backup(&path, &destination).unwrap(), 1);
+    assert_eq!(fs::read_to_string(&destination).unwrap(), text);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_logbooks_back_up_and_verify() {
+    let path = logbook("sqlite").with_extension("sqlite");
+    db::write_flights(
+        &path,
+        &[flight("KSEA", "KPDX", "70"), flight("KPDX", "KBFI", "55")],
+    )
+    .unwrap();
+
+    let (valid, corrupt) = db::verify(&path).unwrap();
+    assert_eq!(valid, 2);
+    assert!(corrupt.is_empty());
+
+    let destination = path.with_file_name("backup.sqlite");
+    assert_eq!(db::backup(&path, &destination).unwrap(), 2);
+    assert_eq!(routes(&destination), ["KSEA-KPDX", "KPDX-KBFI"]);
+}
+
+#[test]
+fn last_flight_is_read_from_the_end() {
+    let path = logbook("last");
+    // Notes long enough that the last record spans more than one read from the end.
+    let mut long = flight("KPDX", "KBFI", "55");
+    long.notes = Some("x".repeat(20_000));
+    db::append_flights(&path, &[flight("KSEA", "KPDX", "70"), long]).unwrap();
+    let mut text = fs::read_to_string(&path).unwrap();
+    text.push_str("\n  \n");
+    fs::write(&path, &text).unwrap();
+
+    let last = db::read_last(&path, false).unwrap().unwrap();
+    assert_eq!(last.waypoints, ["KPDX", "KBFI"]);
+    assert_eq!(last.notes.map(|notes| notes.len()), Some(20_000));
+
+    // A half-written last line falls back to the flight before it.
+    text.push_str("{\"created\": \"2024-01\n");
+    fs::write(&path, &text).unwrap();
+    let last = db::read_last(&path, false).unwrap().unwrap();
+    assert_eq!(last.waypoints, ["KPDX", "KBFI"]);
+}
+
+#[test]
+fn blank_logbook_has_no_last_flight() {
+    let path = logbook("blank");
+    fs::write(&path, "\n \n").unwrap();
+    assert!(db::read_last(&path, false).unwrap().is_none());
+}
+
+#[test]
+fn backup_refuses_the_logbook_itself() {
+    let path = logbook("backup-self");
+    db::append_flights(&path, &[flight("KSEA", "KPDX", "70")]).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+
+    let alias = path.parent().unwrap().join(".").join("db.json");
+    assert!(db::backup(&path, &alias).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), text);
+}
+
+#[test]
+fn backup_refuses_to_overwrite() {
+    let path = logbook("backup-existing");
+    db::append_flights(&path, &[flight("KSEA", "KPDX", "70")]).unwrap();
+
+    let destination = path.with_file_name("backup.json");
+    fs::write(&destination, "keep me").unwrap();
+    let e = db::backup(&path, &destination).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_to_string(&destination).unwrap(), "keep me");
+}
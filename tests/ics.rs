@@ -0,0 +1,26 @@
+use chrono::Utc;
+use route::{elapsed::ElapsedTime, export, flight::Flight};
+
+#[test]
+fn round_trip_legs_get_their_own_uids() {
+    let out = Flight::new("KSEA", ["KPDX"], "1+10".parse::<ElapsedTime>().unwrap());
+    let back = out.reversed("1+05".parse().unwrap());
+    assert_eq!(back.created, out.created + out.elapsed);
+
+    let mut ics = Vec::new();
+    export::write_ics(&mut ics, [&out, &back], Utc::now()).unwrap();
+    let ics = String::from_utf8(ics).unwrap();
+
+    let uids: Vec<_> = ics
+        .lines()
+        .filter(|line| line.starts_with("UID:"))
+        .collect();
+    assert_eq!(
+        uids,
+        [
+            format!("UID:{}@route", out.id),
+            format!("UID:{}@route", back.id)
+        ]
+    );
+    assert_ne!(uids[0], uids[1]);
+}